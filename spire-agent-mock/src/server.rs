@@ -135,7 +135,7 @@ impl SpiffeWorkloadApi for MockWorkloadApi {
     ) -> Result<Response<Self::FetchX509BundlesStream>, Status> {
         println!("Received FetchX509Bundles request");
 
-        let trust_domain = self.svid_generator.trust_domain().to_string();
+        let trust_domain = self.svid_generator.trust_domain().clone();
         let bundle = self.svid_generator.bundle();
         let rotation_interval = self.rotation_interval;
 
@@ -144,7 +144,7 @@ impl SpiffeWorkloadApi for MockWorkloadApi {
                 let response = X509BundlesResponse {
                     crl: vec![],
                     bundles: std::collections::HashMap::from_iter([(
-                        format!("spiffe://{}", trust_domain),
+                        trust_domain.id().to_string(),
                         bundle.clone(),
                     )]),
                 };
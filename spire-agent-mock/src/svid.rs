@@ -2,14 +2,14 @@ use rcgen::{
     BasicConstraints, CertificateParams, DistinguishedName, DnType,
     ExtendedKeyUsagePurpose, IsCa, Issuer, KeyPair, KeyUsagePurpose, SanType,
 };
+use spire_agent::{SpiffeId, TrustDomain};
 use time::{Duration, OffsetDateTime};
 
 use crate::workload::X509svid;
 
 /// Configuration for SVID generation
 pub struct SvidConfig {
-    pub trust_domain: String,
-    pub workload_path: String,
+    pub spiffe_id: SpiffeId,
     pub ttl_seconds: u32,
 }
 
@@ -22,8 +22,9 @@ const DEFAULT_SPIRE_SERVER_CA_VALIDITY_DAYS: i64 = 365;
 impl Default for SvidConfig {
     fn default() -> Self {
         Self {
-            trust_domain: DEFAULT_TRUST_DOMAIN.to_string(),
-            workload_path: DEFAULT_WORKLOAD_PATH.to_string(),
+            spiffe_id: TrustDomain::new(DEFAULT_TRUST_DOMAIN)
+                .and_then(|trust_domain| trust_domain.spiffe_id(DEFAULT_WORKLOAD_PATH))
+                .expect("default SPIFFE ID is valid"),
             ttl_seconds: DEFAULT_TTL_SECONDS,
         }
     }
@@ -40,9 +41,10 @@ pub struct SvidGenerator {
 impl SvidGenerator {
     /// Create a new SVID generator with the given configuration
     pub fn new(config: SvidConfig) -> Self {
-        let (root_issuer, root_cert_der) = Self::generate_root_ca(&config.trust_domain);
+        let trust_domain = config.spiffe_id.trust_domain();
+        let (root_issuer, root_cert_der) = Self::generate_root_ca(trust_domain);
         let (spire_server_issuer, spire_server_cert_der) =
-            Self::generate_spire_server_ca(&config.trust_domain, &root_issuer);
+            Self::generate_spire_server_ca(trust_domain, &root_issuer);
         Self {
             config,
             root_cert_der,
@@ -52,13 +54,13 @@ impl SvidGenerator {
     }
 
     /// Generate a root CA certificate for the trust domain
-    fn generate_root_ca(trust_domain: &str) -> (Issuer<'static, KeyPair>, Vec<u8>) {
+    fn generate_root_ca(trust_domain: &TrustDomain) -> (Issuer<'static, KeyPair>, Vec<u8>) {
         let mut params = CertificateParams::default();
 
         // Set distinguished name
         let mut dn = DistinguishedName::new();
         dn.push(DnType::CommonName, format!("{} Root CA", trust_domain));
-        dn.push(DnType::OrganizationName, trust_domain);
+        dn.push(DnType::OrganizationName, trust_domain.name());
         params.distinguished_name = dn;
 
         // CA certificate settings
@@ -71,7 +73,7 @@ impl SvidGenerator {
         params.not_after = now + Duration::days(DEFAULT_ROOT_CA_VALIDITY_DAYS);
 
         // Add SPIFFE trust domain as URI SAN
-        let trust_domain_uri = format!("spiffe://{}", trust_domain);
+        let trust_domain_uri = trust_domain.id().to_string();
         params.subject_alt_names = vec![SanType::URI(trust_domain_uri.parse().unwrap())];
 
         // Generate key pair
@@ -88,7 +90,7 @@ impl SvidGenerator {
 
     /// Generate a SPIRE Server signing CA certificate signed by the root CA
     fn generate_spire_server_ca(
-        trust_domain: &str,
+        trust_domain: &TrustDomain,
         root_issuer: &Issuer<'static, KeyPair>,
     ) -> (Issuer<'static, KeyPair>, Vec<u8>) {
         let mut params = CertificateParams::default();
@@ -99,7 +101,7 @@ impl SvidGenerator {
             DnType::CommonName,
             format!("{} SPIRE Server CA", trust_domain),
         );
-        dn.push(DnType::OrganizationName, trust_domain);
+        dn.push(DnType::OrganizationName, trust_domain.name());
         params.distinguished_name = dn;
 
         // SPIRE Server signing CA settings
@@ -112,7 +114,7 @@ impl SvidGenerator {
         params.not_after = now + Duration::days(DEFAULT_SPIRE_SERVER_CA_VALIDITY_DAYS);
 
         // Add SPIFFE trust domain as URI SAN
-        let trust_domain_uri = format!("spiffe://{}", trust_domain);
+        let trust_domain_uri = trust_domain.id().to_string();
         params.subject_alt_names = vec![SanType::URI(trust_domain_uri.parse().unwrap())];
 
         // Generate key pair
@@ -129,10 +131,7 @@ impl SvidGenerator {
 
     /// Generate a new X.509 SVID
     pub fn generate_svid(&self) -> X509svid {
-        let spiffe_id = format!(
-            "spiffe://{}{}",
-            self.config.trust_domain, self.config.workload_path
-        );
+        let spiffe_id = self.config.spiffe_id.to_string();
 
         // Create workload certificate parameters
         let mut params = CertificateParams::default();
@@ -140,7 +139,7 @@ impl SvidGenerator {
         // Set distinguished name
         let mut dn = DistinguishedName::new();
         dn.push(DnType::CommonName, &spiffe_id);
-        dn.push(DnType::OrganizationName, &self.config.trust_domain);
+        dn.push(DnType::OrganizationName, self.trust_domain().name());
         params.distinguished_name = dn;
 
         // Leaf certificate (not a CA) - use ExplicitNoCa to ensure Basic Constraints
@@ -185,8 +184,8 @@ impl SvidGenerator {
         }
     }
 
-    pub fn trust_domain(&self) -> &str {
-        &self.config.trust_domain
+    pub fn trust_domain(&self) -> &TrustDomain {
+        self.config.spiffe_id.trust_domain()
     }
 
     pub fn bundle(&self) -> Vec<u8> {
@@ -240,16 +239,15 @@ mod tests {
     #[test]
     fn test_default_config_values() {
         let config = SvidConfig::default();
-        assert_eq!(config.trust_domain, DEFAULT_TRUST_DOMAIN);
-        assert_eq!(config.workload_path, DEFAULT_WORKLOAD_PATH);
+        assert_eq!(config.spiffe_id.trust_domain(), DEFAULT_TRUST_DOMAIN);
+        assert_eq!(config.spiffe_id.path(), DEFAULT_WORKLOAD_PATH);
         assert_eq!(config.ttl_seconds, DEFAULT_TTL_SECONDS);
     }
 
     #[test]
     fn test_custom_config() {
         let config = SvidConfig {
-            trust_domain: "test.domain".to_string(),
-            workload_path: "/my/service".to_string(),
+            spiffe_id: "spiffe://test.domain/my/service".parse().unwrap(),
             ttl_seconds: 60,
         };
         let generator = SvidGenerator::new(config);
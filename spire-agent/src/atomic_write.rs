@@ -0,0 +1,389 @@
+use std::{collections::BTreeMap, fs, io::Write, path::Path};
+
+use anyhow::{Context, Result};
+
+/// A set of files that `--write` switches over together.
+///
+/// On Unix the files are published with the layout Kubernetes uses for
+/// projected volumes, namespaced by the writer: every generation is written
+/// and fsynced into its own hidden `..<writer>_<timestamp>` directory, the
+/// `..<writer>_data` symlink is renamed to point at it, and each visible file
+/// is a symlink through that link. Readers never see a partially written
+/// file, and a certificate is never paired with the key of another
+/// generation. A commit replaces the writer's previous set, so links to its
+/// files that the new set no longer contains are removed; files of other
+/// writers sharing the directory are left alone.
+#[derive(Debug)]
+pub struct FileSet {
+    writer: &'static str,
+    files: BTreeMap<String, PendingFile>,
+}
+
+#[derive(Debug)]
+struct PendingFile {
+    contents: Vec<u8>,
+    mode: u32,
+}
+
+impl FileSet {
+    /// Creates an empty set for `writer`, a short name such as `x509` that is
+    /// unique to the command writing it.
+    pub fn new(writer: &'static str) -> Self {
+        Self {
+            writer,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Queues `name` (a plain file name, not a path) for writing.
+    pub fn add(&mut self, name: impl Into<String>, contents: impl Into<Vec<u8>>, mode: u32) {
+        self.files.insert(
+            name.into(),
+            PendingFile {
+                contents: contents.into(),
+                mode,
+            },
+        );
+    }
+
+    /// Writes every queued file into `dir`, creating it if needed.
+    pub fn commit(&self, dir: &Path) -> Result<()> {
+        if dir.exists() && !dir.is_dir() {
+            anyhow::bail!("write path is not a directory: {}", dir.display());
+        }
+        fs::create_dir_all(dir).context("failed to create output directory")?;
+
+        for name in self.files.keys() {
+            if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+                anyhow::bail!("invalid output file name: {name:?}");
+            }
+        }
+
+        self.publish(dir)
+    }
+
+    #[cfg(unix)]
+    fn publish(&self, dir: &Path) -> Result<()> {
+        use std::os::unix::fs::symlink;
+
+        let data_name = self.data_link_name();
+        let data_link = dir.join(&data_name);
+
+        let generation = format!(
+            "..{}_{}",
+            self.writer,
+            chrono::Utc::now().format("%Y_%m_%d_%H_%M_%S%.9f")
+        );
+        let generation_dir = dir.join(&generation);
+        fs::create_dir(&generation_dir)
+            .with_context(|| format!("failed to create {}", generation_dir.display()))?;
+
+        for (name, file) in &self.files {
+            write_synced(&generation_dir.join(name), &file.contents, file.mode)?;
+        }
+        sync_dir(&generation_dir)?;
+
+        // Renaming a symlink over the data link is the single atomic switch
+        // between generations.
+        let tmp_link = dir.join(format!("{data_name}_tmp"));
+        remove_if_exists(&tmp_link)?;
+        symlink(&generation, &tmp_link)
+            .with_context(|| format!("failed to create {}", tmp_link.display()))?;
+        fs::rename(&tmp_link, &data_link)
+            .with_context(|| format!("failed to update {}", data_link.display()))?;
+
+        for name in self.files.keys() {
+            let target = Path::new(&data_name).join(name);
+            let path = dir.join(name);
+            if fs::read_link(&path).is_ok_and(|current| current == target) {
+                continue;
+            }
+            // Replaces plain files from older releases, and links of another
+            // writer that used the same name, as well.
+            let tmp = dir.join(format!(".{name}.tmp"));
+            remove_if_exists(&tmp)?;
+            symlink(&target, &tmp)
+                .with_context(|| format!("failed to create {}", tmp.display()))?;
+            fs::rename(&tmp, &path)
+                .with_context(|| format!("failed to update {}", path.display()))?;
+        }
+        self.remove_stale_links(dir, &data_name)?;
+        sync_dir(dir)?;
+
+        // Readers holding an old generation open keep their file handles.
+        self.remove_old_generations(dir, &generation);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn data_link_name(&self) -> String {
+        format!("..{}_data", self.writer)
+    }
+
+    // Removes the visible links into this writer's data link whose files are
+    // not part of this set and therefore no longer exist in the current
+    // generation. Anything else in the directory is left alone.
+    #[cfg(unix)]
+    fn remove_stale_links(&self, dir: &Path, data_name: &str) -> Result<()> {
+        let entries =
+            fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("failed to read {}", dir.display()))?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if name.starts_with('.') || self.files.contains_key(name) {
+                continue;
+            }
+            let path = entry.path();
+            if fs::read_link(&path).is_ok_and(|target| target == Path::new(data_name).join(name)) {
+                remove_if_exists(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Removes every generation directory of this writer except `current`,
+    // including ones left behind by a commit that was interrupted before the
+    // switch. Only real directories directly inside `dir` whose names have
+    // the generation format are touched; the data link is never followed.
+    #[cfg(unix)]
+    fn remove_old_generations(&self, dir: &Path, current: &str) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if name == current || !self.is_generation(name) {
+                continue;
+            }
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn is_generation(&self, name: &str) -> bool {
+        let Some(timestamp) = name
+            .strip_prefix("..")
+            .and_then(|name| name.strip_prefix(self.writer))
+            .and_then(|name| name.strip_prefix('_'))
+        else {
+            return false;
+        };
+        timestamp.starts_with(|c: char| c.is_ascii_digit())
+            && timestamp
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '_' || c == '.')
+    }
+
+    #[cfg(not(unix))]
+    fn publish(&self, dir: &Path) -> Result<()> {
+        for (name, file) in &self.files {
+            let path = dir.join(name);
+            let tmp = dir.join(format!(".{name}.tmp"));
+            write_synced(&tmp, &file.contents, file.mode)?;
+            fs::rename(&tmp, &path)
+                .with_context(|| format!("failed to update {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn write_synced(path: &Path, contents: &[u8], mode: u32) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.create_new(true).write(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    file.write_all(contents)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    file.sync_all()
+        .with_context(|| format!("failed to sync {}", path.display()))
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<()> {
+    fs::File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|| format!("failed to sync {}", dir.display()))
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    use super::FileSet;
+
+    const DATA_DIR: &str = "..x509_data";
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spire-agent-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn commit_switches_generations_through_data_symlink() {
+        let dir = temp_dir("atomic-write");
+
+        let mut first = FileSet::new("x509");
+        first.add("svid.0.pem", "cert-1", 0o644);
+        first.add("svid.0.key", "key-1", 0o600);
+        first.commit(&dir).unwrap();
+        let first_generation = fs::read_link(dir.join(DATA_DIR)).unwrap();
+
+        let mut second = FileSet::new("x509");
+        second.add("svid.0.pem", "cert-2", 0o644);
+        second.commit(&dir).unwrap();
+
+        assert_ne!(fs::read_link(dir.join(DATA_DIR)).unwrap(), first_generation);
+        assert!(!dir.join(&first_generation).exists());
+        assert_eq!(
+            fs::read_link(dir.join("svid.0.pem")).unwrap(),
+            PathBuf::from(DATA_DIR).join("svid.0.pem")
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("svid.0.pem")).unwrap(),
+            "cert-2"
+        );
+        let mode = fs::metadata(dir.join("svid.0.pem"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o644);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn commit_removes_files_missing_from_the_new_set() {
+        let dir = temp_dir("atomic-write-shrink");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("unrelated"), "kept").unwrap();
+
+        let mut first = FileSet::new("x509");
+        first.add("a", "a-1", 0o644);
+        first.add("b", "b-1", 0o644);
+        first.commit(&dir).unwrap();
+
+        let mut second = FileSet::new("x509");
+        second.add("a", "a-2", 0o644);
+        second.commit(&dir).unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "a-2");
+        assert!(fs::symlink_metadata(dir.join("b")).is_err());
+        assert!(!dir.join(DATA_DIR).join("b").exists());
+        assert_eq!(fs::read_to_string(dir.join("unrelated")).unwrap(), "kept");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn commit_keeps_files_of_other_writers() {
+        let dir = temp_dir("atomic-write-writers");
+
+        let mut svids = FileSet::new("x509");
+        svids.add("svid.0.pem", "cert-1", 0o644);
+        svids.commit(&dir).unwrap();
+
+        let mut bundles = FileSet::new("bundle");
+        bundles.add("bundle.example.org.pem", "bundle-1", 0o644);
+        bundles.commit(&dir).unwrap();
+
+        let mut svids = FileSet::new("x509");
+        svids.add("svid.0.pem", "cert-2", 0o644);
+        svids.commit(&dir).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dir.join("svid.0.pem")).unwrap(),
+            "cert-2"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("bundle.example.org.pem")).unwrap(),
+            "bundle-1"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn commit_removes_only_its_own_generations() {
+        let dir = temp_dir("atomic-write-sweep");
+        let outside = temp_dir("atomic-write-outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("kept"), "kept").unwrap();
+
+        // A tampered data link and the leftovers of interrupted commits.
+        fs::create_dir_all(&dir).unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join(DATA_DIR)).unwrap();
+        fs::create_dir(dir.join("..x509_2020_01_01_00_00_00.000000000")).unwrap();
+        fs::create_dir(dir.join("..bundle_2020_01_01_00_00_00.000000000")).unwrap();
+
+        let mut files = FileSet::new("x509");
+        files.add("svid.0.pem", "cert", 0o644);
+        files.commit(&dir).unwrap();
+
+        assert_eq!(fs::read_to_string(outside.join("kept")).unwrap(), "kept");
+        assert!(!dir.join("..x509_2020_01_01_00_00_00.000000000").exists());
+        assert!(dir.join("..bundle_2020_01_01_00_00_00.000000000").is_dir());
+        assert_eq!(fs::read_to_string(dir.join("svid.0.pem")).unwrap(), "cert");
+
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_dir_all(outside).unwrap();
+    }
+
+    #[test]
+    fn commit_replaces_plain_files() {
+        let dir = temp_dir("atomic-write-plain");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("bundle.0.pem"), "old").unwrap();
+
+        let mut files = FileSet::new("x509");
+        files.add("bundle.0.pem", "new", 0o644);
+        files.commit(&dir).unwrap();
+
+        assert!(
+            fs::symlink_metadata(dir.join("bundle.0.pem"))
+                .unwrap()
+                .is_symlink()
+        );
+        assert_eq!(fs::read_to_string(dir.join("bundle.0.pem")).unwrap(), "new");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn commit_rejects_paths() {
+        let dir = temp_dir("atomic-write-invalid");
+        let mut files = FileSet::new("x509");
+        files.add("../escape", "x", 0o644);
+        assert!(files.commit(&dir).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
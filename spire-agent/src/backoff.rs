@@ -0,0 +1,93 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// First delay before reopening a Workload API stream.
+pub const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+/// Longest delay between attempts to reopen a Workload API stream.
+pub const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// Spreads out reconnects when many workloads lose the same agent at once.
+pub const RECONNECT_JITTER: f64 = 0.2;
+
+/// Exponential backoff used when re-establishing Workload API streams.
+///
+/// The delay doubles on every call to [`Backoff::next_delay`] until it
+/// reaches `max`, and starts over from `initial` after [`Backoff::reset`].
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+    jitter: f64,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            current: initial,
+            jitter: 0.0,
+        }
+    }
+
+    /// Shortens every delay by a random fraction of up to `jitter` (0.0 to
+    /// 1.0), so that many clients restarted together do not retry in lockstep.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = self.current.saturating_mul(2).min(self.max);
+        if self.jitter > 0.0 {
+            delay.mul_f64(1.0 - self.jitter * random_fraction())
+        } else {
+            delay
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+// A uniformly distributed value in [0, 1). `RandomState` is seeded randomly
+// per instance, which is plenty for spreading out retries.
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Backoff;
+
+    #[test]
+    fn backoff_doubles_up_to_max_and_resets() {
+        let mut backoff = Backoff::new(Duration::from_millis(500), Duration::from_secs(3));
+
+        assert_eq!(backoff.next_delay(), Duration::from_millis(500));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(3));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(3));
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn backoff_jitter_stays_within_bounds() {
+        let mut backoff =
+            Backoff::new(Duration::from_secs(1), Duration::from_secs(1)).with_jitter(0.5);
+
+        for _ in 0..100 {
+            let delay = backoff.next_delay();
+            assert!(delay > Duration::from_millis(500) && delay <= Duration::from_secs(1));
+        }
+    }
+}
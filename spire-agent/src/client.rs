@@ -0,0 +1,215 @@
+use anyhow::{Context, Result};
+use tonic::Streaming;
+use tonic::transport::Channel;
+
+use crate::grpc::{
+    JwtBundlesRequest, JwtBundlesResponse, JwtsvidRequest, ValidateJwtsvidRequest,
+    X509BundlesRequest, X509BundlesResponse, X509svidRequest, X509svidResponse,
+};
+use crate::jwt::{JwtBundleSet, JwtSvid, ValidatedJwtSvid};
+use crate::rpc::{ENDPOINT_SOCKET_ENV, WorkloadClient, connect_workload_client, workload_client};
+use crate::spiffe_id::SpiffeId;
+use crate::x509::{X509BundleSet, X509Context};
+
+/// A client for the SPIFFE Workload API.
+///
+/// Every call adds the `workload.spiffe.io` security header. Failed calls keep
+/// the underlying [`tonic::Status`] in the error chain, so callers can
+/// `downcast_ref` it to tell transient failures from permanent ones. The
+/// client does not time out on its own; wrap calls in
+/// [`tokio::time::timeout`] where a bound is needed.
+#[derive(Debug, Clone)]
+pub struct WorkloadApiClient {
+    client: WorkloadClient,
+}
+
+impl WorkloadApiClient {
+    /// Connects to a Workload API address: a socket path, `unix:///path` or
+    /// `tcp://ip:port`.
+    pub async fn connect(address: &str) -> Result<Self> {
+        Ok(Self {
+            client: connect_workload_client(address).await?,
+        })
+    }
+
+    /// Connects to the address in `SPIFFE_ENDPOINT_SOCKET`.
+    pub async fn connect_env() -> Result<Self> {
+        let address = std::env::var(ENDPOINT_SOCKET_ENV)
+            .with_context(|| format!("{ENDPOINT_SOCKET_ENV} is not set"))?;
+        Self::connect(&address).await
+    }
+
+    /// Wraps an already established channel.
+    pub fn from_channel(channel: Channel) -> Self {
+        Self {
+            client: workload_client(channel),
+        }
+    }
+
+    /// Fetches the workload's current X.509-SVIDs and bundles.
+    pub async fn fetch_x509_context(&mut self) -> Result<X509Context> {
+        self.watch_x509_contexts()
+            .await?
+            .next()
+            .await?
+            .context("empty response from server")
+    }
+
+    /// Streams X.509 contexts: the current one first, then one per rotation
+    /// or bundle change.
+    pub async fn watch_x509_contexts(&mut self) -> Result<X509ContextStream> {
+        let response = self.open_x509svid_stream().await?;
+        Ok(UpdateStream::new(response, |resp| {
+            X509Context::try_from(&resp)
+        }))
+    }
+
+    /// Streams the `X509SVIDResponse` messages as the agent sent them, for
+    /// callers that need the wire format, e.g. to re-emit it unchanged.
+    /// [`X509Context::try_from`] turns one into a context.
+    pub async fn watch_x509_svid_responses(&mut self) -> Result<X509SvidResponseStream> {
+        let response = self.open_x509svid_stream().await?;
+        Ok(UpdateStream::new(response, Ok))
+    }
+
+    async fn open_x509svid_stream(&mut self) -> Result<Streaming<X509svidResponse>> {
+        let response = self
+            .client
+            .fetch_x509svid(X509svidRequest {})
+            .await
+            .context("failed to fetch x509 svid")?;
+        Ok(response.into_inner())
+    }
+
+    /// Fetches the X.509 bundles of every trust domain the workload trusts.
+    pub async fn fetch_x509_bundles(&mut self) -> Result<X509BundleSet> {
+        self.watch_x509_bundles()
+            .await?
+            .next()
+            .await?
+            .context("empty response from server")
+    }
+
+    /// Streams X.509 bundle sets: the current one first, then one per change.
+    pub async fn watch_x509_bundles(&mut self) -> Result<X509BundleSetStream> {
+        let response = self
+            .client
+            .fetch_x509_bundles(X509BundlesRequest {})
+            .await
+            .context("failed to fetch x509 bundles")?;
+        Ok(UpdateStream::new(response.into_inner(), |resp| {
+            X509BundleSet::try_from(&resp)
+        }))
+    }
+
+    /// Fetches JWT-SVIDs for `audience`: one per identity the workload has,
+    /// or only the one for `spiffe_id` when given.
+    pub async fn fetch_jwt_svids(
+        &mut self,
+        audience: &[String],
+        spiffe_id: Option<&SpiffeId>,
+    ) -> Result<Vec<JwtSvid>> {
+        let request = JwtsvidRequest {
+            audience: audience.to_vec(),
+            spiffe_id: spiffe_id.map(ToString::to_string).unwrap_or_default(),
+        };
+        let response = self
+            .client
+            .fetch_jwtsvid(request)
+            .await
+            .context("failed to fetch jwt svid")?;
+
+        response
+            .into_inner()
+            .svids
+            .iter()
+            .map(JwtSvid::try_from)
+            .collect()
+    }
+
+    /// Fetches a single JWT-SVID for `audience`; the default identity unless
+    /// `spiffe_id` picks another.
+    pub async fn fetch_jwt_svid(
+        &mut self,
+        audience: &[String],
+        spiffe_id: Option<&SpiffeId>,
+    ) -> Result<JwtSvid> {
+        self.fetch_jwt_svids(audience, spiffe_id)
+            .await?
+            .into_iter()
+            .next()
+            .context("empty response from server")
+    }
+
+    /// Fetches the JWT bundles of every trust domain the workload trusts.
+    pub async fn fetch_jwt_bundles(&mut self) -> Result<JwtBundleSet> {
+        self.watch_jwt_bundles()
+            .await?
+            .next()
+            .await?
+            .context("empty response from server")
+    }
+
+    /// Streams JWT bundle sets: the current one first, then one per change.
+    pub async fn watch_jwt_bundles(&mut self) -> Result<JwtBundleSetStream> {
+        let response = self
+            .client
+            .fetch_jwt_bundles(JwtBundlesRequest {})
+            .await
+            .context("failed to fetch jwt bundles")?;
+        Ok(UpdateStream::new(response.into_inner(), |resp| {
+            JwtBundleSet::try_from(&resp)
+        }))
+    }
+
+    /// Asks the agent to validate a JWT-SVID for `audience`.
+    ///
+    /// A token the agent rejects fails with `InvalidArgument`.
+    pub async fn validate_jwt_svid(
+        &mut self,
+        token: &str,
+        audience: &str,
+    ) -> Result<ValidatedJwtSvid> {
+        let request = ValidateJwtsvidRequest {
+            audience: audience.to_string(),
+            svid: token.to_string(),
+        };
+        let response = self.client.validate_jwtsvid(request).await?;
+        ValidatedJwtSvid::try_from(response.get_ref())
+    }
+}
+
+/// Wraps a generated client, e.g. one built by
+/// [`rpc::connect_workload_client`](crate::rpc::connect_workload_client).
+impl From<WorkloadClient> for WorkloadApiClient {
+    fn from(client: WorkloadClient) -> Self {
+        Self { client }
+    }
+}
+
+/// Typed updates from one of the Workload API's server streams.
+pub struct UpdateStream<M, T> {
+    stream: Streaming<M>,
+    convert: fn(M) -> Result<T>,
+}
+
+pub type X509ContextStream = UpdateStream<X509svidResponse, X509Context>;
+pub type X509SvidResponseStream = UpdateStream<X509svidResponse, X509svidResponse>;
+pub type X509BundleSetStream = UpdateStream<X509BundlesResponse, X509BundleSet>;
+pub type JwtBundleSetStream = UpdateStream<JwtBundlesResponse, JwtBundleSet>;
+
+impl<M, T> UpdateStream<M, T> {
+    fn new(stream: Streaming<M>, convert: fn(M) -> Result<T>) -> Self {
+        Self { stream, convert }
+    }
+
+    /// Waits for the next update; `None` once the agent closes the stream.
+    pub async fn next(&mut self) -> Result<Option<T>> {
+        let message = self
+            .stream
+            .message()
+            .await
+            .context("failed to receive message")?;
+        message.map(self.convert).transpose()
+    }
+}
@@ -1,10 +1,25 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
-use anyhow::{Context, Result};
+use anyhow::{Result, anyhow};
 use clap::{CommandFactory, Parser, Subcommand};
+use spire_agent::SpiffeId;
+use spire_agent::rpc::{ENDPOINT_SOCKET_ENV, WorkloadAddress};
 
-use crate::fetch_x509::fetch_x509;
-use crate::healthcheck::healthcheck;
+use crate::compat::{exit_with_go_usage, normalize_args};
+use crate::fetch_bundle::fetch_bundle;
+use crate::fetch_jwt::fetch_jwt;
+use crate::fetch_jwt_bundle::fetch_jwt_bundle;
+use crate::daemon::{DaemonOptions, run_daemon};
+use crate::duration::parse_go_duration;
+use crate::expiry::{MIN_TTL_EXIT_CODE, MinTtlError};
+use crate::fetch_x509::{FetchX509Options, fetch_x509};
+use crate::healthcheck::{HealthcheckError, healthcheck};
+use crate::notify::{Notifier, SignalTarget, parse_signal};
+use crate::output::{OutputFormat, WriteFormat};
+use crate::retry::RetryPolicy;
+use crate::pkcs12::PASSPHRASE_ENV;
+use crate::validate_jwt::validate_jwt;
+use crate::watch::watch_x509;
 
 #[derive(Parser)]
 #[command(name = "spire-agent", version, about = "Agent CLI for Spire")]
@@ -24,19 +39,23 @@ struct ApiArgs {
     #[arg(
         long = "output",
         value_name = "value",
+        value_enum,
         default_value = "pretty",
+        hide_possible_values = true,
         global = true,
         help = "Desired output format (pretty, json); default: pretty."
     )]
-    output: String,
+    output: OutputFormat,
     #[arg(long = "silent", global = true, help = "Suppress stdout")]
     silent: bool,
     #[arg(
         long = "socket-path",
         value_name = "string",
         default_value = "/tmp/spire-agent/public/api.sock",
+        env = ENDPOINT_SOCKET_ENV,
+        value_parser = validate_address,
         global = true,
-        help = "Path to the SPIRE Agent API Unix domain socket (default \"/tmp/spire-agent/public/api.sock\")"
+        help = "Path or unix:// / tcp:// URI of the SPIRE Agent API socket (default \"/tmp/spire-agent/public/api.sock\")"
     )]
     socket_path: String,
     #[arg(
@@ -54,11 +73,47 @@ struct ApiArgs {
         help = "Write SVID data to the specified path (optional; only available for pretty output format)"
     )]
     write: Option<String>,
+    #[command(flatten)]
+    retry: RetryArgs,
     #[command(subcommand)]
     command: ApiCommand,
 }
 
 #[derive(Parser)]
+struct RetryArgs {
+    #[arg(
+        long = "retry-deadline",
+        value_name = "value",
+        default_value = "0s",
+        value_parser = parse_duration,
+        global = true,
+        help = "Retry while the agent is unavailable or has not issued an identity, for up to this long (default 0s: no retries; watch and daemon retry until their first update)"
+    )]
+    deadline: Duration,
+    #[arg(
+        long = "retry-max-delay",
+        value_name = "value",
+        default_value = "5s",
+        value_parser = parse_duration,
+        global = true,
+        help = "Longest delay between two retries (default 5s)"
+    )]
+    max_delay: Duration,
+}
+
+impl RetryArgs {
+    fn policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            deadline: self.deadline,
+            max_delay: self.max_delay,
+        }
+    }
+}
+
+#[derive(Parser)]
+#[command(after_help = "Exit status: 0 healthy, 1 other failure, 3 socket missing, \
+4 connection refused, 5 timed out, 6 health request failed, 7 not serving, \
+8 X509-SVID fetch failed")]
 struct HealthcheckArgs {
     #[arg(long = "shallow", help = "Perform a less stringent health check")]
     shallow: bool,
@@ -67,19 +122,87 @@ struct HealthcheckArgs {
         alias = "socketPath",
         value_name = "string",
         default_value = "/tmp/spire-agent/public/api.sock",
-        help = "Path to the SPIRE Agent API socket (default \"/tmp/spire-agent/public/api.sock\")"
+        env = ENDPOINT_SOCKET_ENV,
+        value_parser = validate_address,
+        help = "Path or unix:// / tcp:// URI of the SPIRE Agent API socket (default \"/tmp/spire-agent/public/api.sock\")"
     )]
     socket_path: String,
+    #[arg(
+        long = "timeout",
+        value_name = "value",
+        default_value = "1s",
+        value_parser = parse_duration,
+        help = "Time to wait for each check (default 1s)"
+    )]
+    timeout: Duration,
     #[arg(long = "verbose", help = "Print verbose information")]
     verbose: bool,
+    #[arg(
+        long = "output",
+        value_name = "value",
+        value_enum,
+        default_value = "pretty",
+        hide_possible_values = true,
+        help = "Desired output format (pretty, json); default: pretty."
+    )]
+    output: OutputFormat,
+    #[command(flatten)]
+    retry: RetryArgs,
 }
 
 #[derive(Subcommand)]
 enum ApiCommand {
+    Daemon(DaemonArgs),
     Fetch(FetchArgs),
+    Validate(ValidateArgs),
     Watch,
 }
 
+#[derive(Parser)]
+struct DaemonArgs {
+    #[arg(
+        long = "jwt-audience",
+        value_name = "value",
+        value_delimiter = ',',
+        help = "Also keep JWT-SVIDs for this comma separated list of audiences in jwt_svid.N.token"
+    )]
+    jwt_audience: Vec<String>,
+    #[arg(
+        long = "signal",
+        value_name = "value",
+        default_value = "SIGHUP",
+        value_parser = parse_signal,
+        requires = "signal_target",
+        help = "Signal sent after every update (default SIGHUP); needs --pid-file or --process-name"
+    )]
+    signal: i32,
+    #[arg(
+        long = "pid-file",
+        value_name = "path",
+        group = "signal_target",
+        help = "Send --signal to the process whose PID is in this file after every update"
+    )]
+    pid_file: Option<PathBuf>,
+    #[arg(
+        long = "process-name",
+        value_name = "string",
+        group = "signal_target",
+        help = "Send --signal to every process with this name after every update"
+    )]
+    process_name: Option<String>,
+    #[arg(
+        long = "exit-after-write",
+        help = "Exit after the first successful write, e.g. in an init container"
+    )]
+    exit_after_write: bool,
+    #[arg(
+        last = true,
+        value_name = "command",
+        help = "Command to run after every update"
+    )]
+    command: Vec<String>,
+}
+
 #[derive(Parser)]
 struct FetchArgs {
     #[command(subcommand)]
@@ -88,43 +211,161 @@ struct FetchArgs {
 
 #[derive(Subcommand)]
 enum FetchCommand {
-    X509,
+    X509(FetchX509Args),
+    Jwt(FetchJwtArgs),
+    Bundle,
+    JwtBundle(FetchJwtBundleArgs),
 }
 
+#[derive(Parser, Default)]
+struct FetchX509Args {
+    #[arg(
+        long = "inspect",
+        help = "Print detailed information about every certificate in the SVID chain and bundle (pretty output only)"
+    )]
+    inspect: bool,
+    #[arg(
+        long = "verify",
+        help = "Verify each SVID chain against its bundle, the private key and the SPIFFE ID"
+    )]
+    verify: bool,
+    #[arg(
+        long = "min-ttl",
+        value_name = "value",
+        value_parser = parse_duration,
+        help = format!(
+            "Exit with status {MIN_TTL_EXIT_CODE} if any leaf, intermediate or bundle CA \
+             expires within this duration"
+        )
+    )]
+    min_ttl: Option<Duration>,
+    #[arg(
+        long = "format",
+        value_name = "value",
+        value_enum,
+        default_value = "pem",
+        hide_possible_values = true,
+        help = "File format for --write (pem, pkcs12); default: pem."
+    )]
+    format: WriteFormat,
+    #[arg(
+        long = "passphrase",
+        value_name = "string",
+        help = format!(
+            "Passphrase protecting PKCS#12 output; {PASSPHRASE_ENV} is used when neither \
+             passphrase flag is given"
+        )
+    )]
+    passphrase: Option<String>,
+    #[arg(
+        long = "passphrase-file",
+        value_name = "path",
+        conflicts_with = "passphrase",
+        help = "File whose first line is the passphrase protecting PKCS#12 output"
+    )]
+    passphrase_file: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct FetchJwtArgs {
+    #[arg(
+        long = "audience",
+        value_name = "value",
+        value_delimiter = ',',
+        required = true,
+        help = "comma separated list of audience values"
+    )]
+    audience: Vec<String>,
+    #[arg(
+        long = "spiffe-id",
+        alias = "spiffeID",
+        value_name = "string",
+        help = "SPIFFE ID subject (optional)"
+    )]
+    spiffe_id: Option<SpiffeId>,
+}
+
+#[derive(Parser)]
+struct FetchJwtBundleArgs {
+    #[arg(
+        long = "spiffe-bundle",
+        help = "Also write a SPIFFE bundle per trust domain holding both X.509 and JWT authorities (requires --write)"
+    )]
+    spiffe_bundle: bool,
+}
+
+#[derive(Parser)]
+struct ValidateArgs {
+    #[command(subcommand)]
+    command: ValidateCommand,
+}
+
+#[derive(Subcommand)]
+enum ValidateCommand {
+    Jwt(ValidateJwtArgs),
+}
+
+#[derive(Parser)]
+struct ValidateJwtArgs {
+    #[arg(
+        long = "audience",
+        value_name = "string",
+        required = true,
+        help = "expected audience value"
+    )]
+    audience: String,
+    #[arg(
+        long = "svid",
+        value_name = "string",
+        required = true,
+        help = "JWT SVID"
+    )]
+    svid: String,
+}
+
+// Go duration syntax, as accepted by the Go spire-agent. Negative values make
+// no sense for timeouts, TTLs or delays, so they are rejected here.
 fn parse_duration(s: &str) -> Result<Duration> {
-    let s = s.trim();
-    let (value, unit) = split_duration(s)?;
-
-    match unit {
-        "" | "s" => Ok(Duration::from_secs(value)),
-        "ns" => Ok(Duration::from_nanos(value)),
-        "us" | "µs" => Ok(Duration::from_micros(value)),
-        "ms" => Ok(Duration::from_millis(value)),
-        "m" => Ok(Duration::from_secs(value.saturating_mul(60))),
-        "h" => Ok(Duration::from_secs(value.saturating_mul(60 * 60))),
-        _ => anyhow::bail!("invalid duration"),
+    let nanos = parse_go_duration(s.trim())?;
+    if nanos < 0 {
+        anyhow::bail!("duration {s:?} must not be negative");
     }
+    Ok(Duration::from_nanos(nanos as u64))
 }
 
-fn split_duration(s: &str) -> Result<(u64, &str)> {
-    if s.is_empty() {
-        anyhow::bail!("invalid duration");
-    }
+// Rejects malformed addresses up front while keeping the address as the
+// string the rest of the CLI passes around.
+fn validate_address(address: &str) -> Result<String> {
+    // clap only prints the outermost message, so flatten the chain.
+    WorkloadAddress::parse(address).map_err(|err| anyhow!("{err:#}"))?;
+    Ok(address.to_string())
+}
 
-    let split_idx = s
-        .find(|c: char| !c.is_ascii_digit())
-        .unwrap_or(s.len());
-    let (value_str, unit) = s.split_at(split_idx);
-    if value_str.is_empty() {
-        anyhow::bail!("invalid duration");
+fn parse_timeout_or_exit(timeout: &str) -> Duration {
+    match parse_duration(timeout) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Error parsing timeout: {e}");
+            std::process::exit(1);
+        }
     }
+}
 
-    let value: u64 = value_str.parse().context("invalid duration")?;
-    Ok((value, unit))
+// Accepts the Go CLI's flag spellings as well as our own, so the binary can
+// replace the Go one without touching existing invocations.
+fn parse_cli() -> Cli {
+    let mut cmd = Cli::command();
+    cmd.build();
+    let normalized = normalize_args(&cmd, std::env::args_os());
+    match Cli::try_parse_from(&normalized.args) {
+        Ok(cli) => cli,
+        Err(err) if normalized.go_style => exit_with_go_usage(&cmd, &normalized.args, &err),
+        Err(err) => err.exit(),
+    }
 }
 
 pub async fn run() {
-    let cli = Cli::parse();
+    let cli = parse_cli();
     match cli.command {
         Some(Commands::Api(ApiArgs {
             command: ApiCommand::Fetch(FetchArgs { command }),
@@ -133,42 +374,200 @@ pub async fn run() {
             silent,
             write,
             output,
+            retry,
         })) => {
-            if output != "pretty" {
-                eprintln!("Error: only pretty output is supported for api fetch");
-                std::process::exit(1);
-            }
-            let timeout = match parse_duration(&timeout) {
-                Ok(d) => d,
-                Err(e) => {
-                    eprintln!("Error parsing timeout: {e}");
-                    std::process::exit(1);
-                }
-            };
+            let timeout = parse_timeout_or_exit(&timeout);
+            let retry = retry.policy();
 
-            match command.unwrap_or(FetchCommand::X509) {
-                FetchCommand::X509 => {
-                    if let Err(e) = fetch_x509(&socket_path, timeout, silent, write.as_deref()).await
+            match command.unwrap_or_else(|| FetchCommand::X509(FetchX509Args::default())) {
+                FetchCommand::X509(FetchX509Args {
+                    inspect,
+                    verify,
+                    min_ttl,
+                    format,
+                    passphrase,
+                    passphrase_file,
+                }) => {
+                    let options = FetchX509Options {
+                        inspect,
+                        verify,
+                        min_ttl,
+                        write_format: format,
+                        passphrase,
+                        passphrase_file,
+                    };
+                    if let Err(e) = fetch_x509(
+                        &socket_path,
+                        timeout,
+                        &retry,
+                        silent,
+                        write.as_deref(),
+                        output,
+                        &options,
+                    )
+                    .await
+                    {
+                        eprintln!("Error: {e}");
+                        if e.downcast_ref::<MinTtlError>().is_some() {
+                            std::process::exit(MIN_TTL_EXIT_CODE);
+                        }
+                        std::process::exit(1);
+                    }
+                }
+                FetchCommand::Bundle => {
+                    if let Err(e) = fetch_bundle(
+                        &socket_path,
+                        timeout,
+                        &retry,
+                        silent,
+                        write.as_deref(),
+                        output,
+                    )
+                    .await
                     {
                         eprintln!("Error: {e}");
                         std::process::exit(1);
                     }
                 }
+                FetchCommand::JwtBundle(FetchJwtBundleArgs { spiffe_bundle }) => {
+                    if let Err(e) = fetch_jwt_bundle(
+                        &socket_path,
+                        timeout,
+                        &retry,
+                        silent,
+                        write.as_deref(),
+                        output,
+                        spiffe_bundle,
+                    )
+                    .await
+                    {
+                        eprintln!("Error: {e}");
+                        std::process::exit(1);
+                    }
+                }
+                FetchCommand::Jwt(FetchJwtArgs {
+                    audience,
+                    spiffe_id,
+                }) => {
+                    if let Err(e) = fetch_jwt(
+                        &socket_path,
+                        timeout,
+                        &retry,
+                        silent,
+                        output,
+                        &audience,
+                        spiffe_id.as_ref(),
+                    )
+                    .await
+                    {
+                        eprintln!("Error: {e}");
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        Some(Commands::Api(ApiArgs {
+            command:
+                ApiCommand::Validate(ValidateArgs {
+                    command: ValidateCommand::Jwt(ValidateJwtArgs { audience, svid }),
+                }),
+            socket_path,
+            timeout,
+            silent,
+            output,
+            retry,
+            ..
+        })) => {
+            let timeout = parse_timeout_or_exit(&timeout);
+            let retry = retry.policy();
+
+            if let Err(e) =
+                validate_jwt(&socket_path, timeout, &retry, silent, output, &audience, &svid).await
+            {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Api(ApiArgs {
+            command:
+                ApiCommand::Daemon(DaemonArgs {
+                    jwt_audience,
+                    signal,
+                    pid_file,
+                    process_name,
+                    exit_after_write,
+                    command,
+                }),
+            socket_path,
+            timeout,
+            silent,
+            write,
+            retry,
+            ..
+        })) => {
+            let timeout = parse_timeout_or_exit(&timeout);
+            let retry = retry.policy();
+            let Some(write_dir) = write else {
+                eprintln!("Error: api daemon requires --write");
+                std::process::exit(1);
+            };
+
+            let target = match (pid_file, process_name) {
+                (Some(path), _) => Some(SignalTarget::PidFile(path)),
+                (None, Some(name)) => Some(SignalTarget::ProcessName(name)),
+                (None, None) => None,
+            };
+            let options = DaemonOptions {
+                write_dir,
+                jwt_audience,
+                notifier: Notifier {
+                    command,
+                    signal: target.map(|target| (signal, target)),
+                },
+                exit_after_write,
+            };
+            if let Err(e) = run_daemon(&socket_path, timeout, &retry, silent, &options).await {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
             }
         }
         Some(Commands::Api(ApiArgs {
             command: ApiCommand::Watch,
+            socket_path,
+            timeout,
+            silent,
+            output,
+            retry,
             ..
         })) => {
-            // Intentionally no-op for now.
+            let timeout = parse_timeout_or_exit(&timeout);
+            let retry = retry.policy();
+
+            if let Err(e) = watch_x509(&socket_path, timeout, &retry, silent, output).await {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
         }
         Some(Commands::Healthcheck(HealthcheckArgs {
             shallow,
             socket_path,
+            timeout,
             verbose,
+            output,
+            retry,
         })) => {
-            if let Err(e) = healthcheck(&socket_path, shallow, verbose).await {
-                eprintln!("Error: {e}");
+            let retry = retry.policy();
+            if let Err(e) =
+                healthcheck(&socket_path, timeout, &retry, output, shallow, verbose).await
+            {
+                if verbose {
+                    eprintln!("Error: {e:#}");
+                } else {
+                    eprintln!("Error: {e}");
+                }
+                if let Some(err) = e.downcast_ref::<HealthcheckError>() {
+                    std::process::exit(err.exit_code());
+                }
                 std::process::exit(1);
             }
         }
@@ -184,10 +583,18 @@ pub async fn run() {
 
 #[cfg(test)]
 mod tests {
+    use std::ffi::OsString;
+    use std::path::PathBuf;
     use std::time::Duration;
 
-    use super::{ApiArgs, ApiCommand, Cli, Commands, FetchArgs, parse_duration};
-    use clap::Parser;
+    use super::{
+        ApiArgs, ApiCommand, Cli, Commands, FetchArgs, FetchCommand, FetchJwtArgs, FetchX509Args,
+        HealthcheckArgs, parse_duration,
+    };
+    use crate::compat::normalize_args;
+    use crate::output::OutputFormat;
+    use crate::retry::RetryPolicy;
+    use clap::{CommandFactory, Parser};
 
     #[test]
     fn parse_duration_supports_units() {
@@ -195,13 +602,19 @@ mod tests {
         assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
         assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
         assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
-        assert_eq!(parse_duration("10").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("1m30s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("500us").unwrap(), Duration::from_micros(500));
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
     }
 
     #[test]
     fn parse_duration_rejects_invalid() {
         assert!(parse_duration("abc").is_err());
         assert!(parse_duration("1xs").is_err());
+        // Like Go, a unit is required for anything but zero.
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("-1s").is_err());
     }
 
     #[test]
@@ -215,4 +628,247 @@ mod tests {
             _ => panic!("unexpected parse result"),
         }
     }
+
+    #[test]
+    fn api_output_accepts_json() {
+        let cli =
+            Cli::try_parse_from(["spire-agent", "api", "fetch", "x509", "--output", "json"]).unwrap();
+        match cli.command {
+            Some(Commands::Api(ApiArgs { output, .. })) => assert_eq!(output, OutputFormat::Json),
+            _ => panic!("unexpected parse result"),
+        }
+
+        assert!(
+            Cli::try_parse_from(["spire-agent", "api", "fetch", "--output", "yaml"]).is_err()
+        );
+    }
+
+    #[test]
+    fn go_flag_spellings_parse() {
+        let mut cmd = Cli::command();
+        cmd.build();
+        let args = [
+            "spire-agent",
+            "api",
+            "fetch",
+            "jwt",
+            "-socketPath",
+            "/tmp/agent.sock",
+            "-audience",
+            "a,b",
+            "-spiffeID=spiffe://example.org/w",
+            "-output",
+            "json",
+            "-timeout=5s",
+            "-silent=true",
+        ];
+        let normalized = normalize_args(&cmd, args.map(OsString::from));
+        assert!(normalized.go_style);
+        match Cli::try_parse_from(normalized.args).unwrap().command {
+            Some(Commands::Api(ApiArgs {
+                command:
+                    ApiCommand::Fetch(FetchArgs {
+                        command: Some(FetchCommand::Jwt(FetchJwtArgs { audience, spiffe_id })),
+                    }),
+                socket_path,
+                timeout,
+                silent,
+                output,
+                ..
+            })) => {
+                assert_eq!(socket_path, "/tmp/agent.sock");
+                assert_eq!(audience, ["a", "b"]);
+                assert_eq!(spiffe_id.unwrap(), "spiffe://example.org/w");
+                assert_eq!(output, OutputFormat::Json);
+                assert_eq!(timeout, "5s");
+                assert!(silent);
+            }
+            _ => panic!("unexpected parse result"),
+        }
+    }
+
+    #[test]
+    fn retry_flags_parse_into_policy() {
+        let cli = Cli::try_parse_from([
+            "spire-agent",
+            "api",
+            "fetch",
+            "x509",
+            "--retry-deadline",
+            "30s",
+            "--retry-max-delay",
+            "2s",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Api(ApiArgs { retry, .. })) => {
+                let policy = retry.policy();
+                assert_eq!(policy.deadline, Duration::from_secs(30));
+                assert_eq!(policy.max_delay, Duration::from_secs(2));
+            }
+            _ => panic!("unexpected parse result"),
+        }
+
+        let cli = Cli::try_parse_from(["spire-agent", "healthcheck"]).unwrap();
+        match cli.command {
+            Some(Commands::Healthcheck(HealthcheckArgs { retry, .. })) => {
+                assert_eq!(retry.policy(), RetryPolicy::default());
+            }
+            _ => panic!("unexpected parse result"),
+        }
+    }
+
+    #[test]
+    fn api_fetch_jwt_splits_audience() {
+        let cli = Cli::try_parse_from([
+            "spire-agent",
+            "api",
+            "fetch",
+            "jwt",
+            "--audience",
+            "a,b",
+            "--spiffeID",
+            "spiffe://example.org/w",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Api(ApiArgs {
+                command:
+                    ApiCommand::Fetch(FetchArgs {
+                        command: Some(FetchCommand::Jwt(FetchJwtArgs { audience, spiffe_id })),
+                    }),
+                ..
+            })) => {
+                assert_eq!(audience, ["a", "b"]);
+                assert_eq!(spiffe_id.unwrap(), "spiffe://example.org/w");
+            }
+            _ => panic!("unexpected parse result"),
+        }
+
+        assert!(Cli::try_parse_from(["spire-agent", "api", "fetch", "jwt"]).is_err());
+        assert!(
+            Cli::try_parse_from([
+                "spire-agent",
+                "api",
+                "fetch",
+                "jwt",
+                "--audience",
+                "a",
+                "--spiffeID",
+                "spiffe://example.org/w/",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn api_fetch_x509_parses_min_ttl() {
+        let cli =
+            Cli::try_parse_from(["spire-agent", "api", "fetch", "x509", "--min-ttl", "10m"]).unwrap();
+        match cli.command {
+            Some(Commands::Api(ApiArgs {
+                command:
+                    ApiCommand::Fetch(FetchArgs {
+                        command: Some(FetchCommand::X509(FetchX509Args { min_ttl, .. })),
+                    }),
+                ..
+            })) => assert_eq!(min_ttl, Some(Duration::from_secs(600))),
+            _ => panic!("unexpected parse result"),
+        }
+
+        assert!(
+            Cli::try_parse_from(["spire-agent", "api", "fetch", "x509", "--min-ttl", "soon"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn api_fetch_x509_passphrase_file_is_independent_of_env() {
+        // The passphrase environment variable is read when writing, not by
+        // clap, so it cannot make --passphrase-file conflict with --passphrase.
+        let args = ["spire-agent", "api", "fetch", "x509", "--passphrase-file", "/tmp/pp"];
+        match Cli::try_parse_from(args).unwrap().command {
+            Some(Commands::Api(ApiArgs {
+                command:
+                    ApiCommand::Fetch(FetchArgs {
+                        command:
+                            Some(FetchCommand::X509(FetchX509Args {
+                                passphrase,
+                                passphrase_file,
+                                ..
+                            })),
+                    }),
+                ..
+            })) => {
+                assert_eq!(passphrase, None);
+                assert_eq!(passphrase_file, Some(PathBuf::from("/tmp/pp")));
+            }
+            _ => panic!("unexpected parse result"),
+        }
+
+        let both = [
+            "spire-agent",
+            "api",
+            "fetch",
+            "x509",
+            "--passphrase",
+            "secret",
+            "--passphrase-file",
+            "/tmp/pp",
+        ];
+        assert!(Cli::try_parse_from(both).is_err());
+    }
+
+    #[test]
+    fn api_daemon_parses_notification_flags() {
+        let cli = Cli::try_parse_from([
+            "spire-agent",
+            "api",
+            "daemon",
+            "--write",
+            "/certs",
+            "--signal",
+            "USR1",
+            "--process-name",
+            "nginx",
+            "--",
+            "nginx",
+            "-s",
+            "reload",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Api(ApiArgs {
+                command: ApiCommand::Daemon(args),
+                write,
+                ..
+            })) => {
+                assert_eq!(write.as_deref(), Some("/certs"));
+                assert_eq!(args.signal, libc::SIGUSR1);
+                assert_eq!(args.process_name.as_deref(), Some("nginx"));
+                assert_eq!(args.command, ["nginx", "-s", "reload"]);
+            }
+            _ => panic!("unexpected parse result"),
+        }
+
+        assert!(
+            Cli::try_parse_from([
+                "spire-agent",
+                "api",
+                "daemon",
+                "--pid-file",
+                "/run/nginx.pid",
+                "--process-name",
+                "nginx",
+            ])
+            .is_err()
+        );
+        // A signal with nobody to send it to is a mistake, not a no-op.
+        assert!(
+            Cli::try_parse_from(["spire-agent", "api", "daemon", "--signal", "USR1"]).is_err()
+        );
+        assert!(
+            Cli::try_parse_from(["spire-agent", "api", "daemon", "--exit-after-write"]).is_ok()
+        );
+    }
 }
@@ -0,0 +1,463 @@
+use std::collections::BTreeMap;
+use std::error::Error as _;
+use std::ffi::OsString;
+
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{Arg, ArgAction, Command};
+
+/// Exit code the Go `spire-agent` uses for every usage error.
+pub const USAGE_EXIT_CODE: i32 = 1;
+
+/// Command line rewritten into the spelling clap understands.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NormalizedArgs {
+    pub args: Vec<OsString>,
+    /// At least one flag used the Go spelling (`-socketPath`), so errors and
+    /// help should be printed the way the Go CLI prints them.
+    pub go_style: bool,
+}
+
+/// Rewrites Go `flag` package spellings into clap long options.
+///
+/// Accepts `-socketPath x`, `-socketPath=x`, `--socketPath x` and
+/// `-socket-path x` for `--socket-path x`, and `-silent=true` or
+/// `-silent=false` for boolean flags. Only names that the subcommand selected
+/// so far defines are rewritten; everything after `--` is passed through
+/// untouched.
+pub fn normalize_args(cmd: &Command, args: impl IntoIterator<Item = OsString>) -> NormalizedArgs {
+    let mut cmd = cmd.clone();
+    // Building adds --help and --version and propagates global options.
+    cmd.build();
+    let mut current = &cmd;
+    let mut flags = known_flags(current);
+    let mut args = args.into_iter();
+    let mut normalized: Vec<OsString> = args.next().into_iter().collect();
+    let mut go_style = false;
+    let mut expect_value = false;
+
+    while let Some(arg) = args.next() {
+        if expect_value {
+            expect_value = false;
+            normalized.push(arg);
+            continue;
+        }
+        let Some(text) = arg.to_str() else {
+            normalized.push(arg);
+            continue;
+        };
+        if text == "--" {
+            normalized.push(arg);
+            normalized.extend(args.by_ref());
+            break;
+        }
+        let (body, single_dash) = match text.strip_prefix("--") {
+            Some(body) => (body, false),
+            None => match text.strip_prefix('-') {
+                // `-h` and other single letters are clap short options.
+                Some(body) if body.len() > 1 => (body, true),
+                Some(_) => {
+                    normalized.push(arg);
+                    continue;
+                }
+                None => {
+                    if let Some(subcommand) = current.find_subcommand(text) {
+                        current = subcommand;
+                        flags = known_flags(current);
+                    }
+                    normalized.push(arg);
+                    continue;
+                }
+            },
+        };
+        let (name, value) = match body.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (body, None),
+        };
+        let long = long_name(name);
+        let Some(&takes_value) = flags.get(long.as_str()) else {
+            // Left for clap to reject, but reported the Go way. Without the
+            // second dash clap would complain about the first letter only.
+            if single_dash && name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                go_style = true;
+                normalized.push(format!("--{body}").into());
+            } else {
+                normalized.push(arg);
+            }
+            continue;
+        };
+        go_style |= single_dash;
+
+        match (takes_value, value) {
+            (true, Some(value)) => normalized.push(format!("--{long}={value}").into()),
+            (true, None) => {
+                normalized.push(format!("--{long}").into());
+                expect_value = true;
+            }
+            (false, None) => normalized.push(format!("--{long}").into()),
+            (false, Some(value)) => match parse_go_bool(value) {
+                Some(true) => normalized.push(format!("--{long}").into()),
+                Some(false) => {}
+                // Let clap report the unexpected value.
+                None => normalized.push(format!("--{long}={value}").into()),
+            },
+        }
+    }
+
+    NormalizedArgs {
+        args: normalized,
+        go_style,
+    }
+}
+
+/// Prints a parse error the way the Go CLI does and exits: `-help` prints
+/// the usage and exits 0, anything else prints the error followed by the
+/// usage and exits 1.
+pub fn exit_with_go_usage(cmd: &Command, args: &[OsString], err: &clap::Error) -> ! {
+    let (name, subcommand) = resolve_subcommand(cmd, args);
+    match err.kind() {
+        ErrorKind::DisplayHelp => {
+            eprint!("{}", go_usage(&name, subcommand));
+            std::process::exit(0);
+        }
+        ErrorKind::DisplayVersion | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => {
+            exit_with_clap_error(err)
+        }
+        _ => {
+            eprintln!("{}", go_error(err));
+            eprint!("{}", go_usage(&name, subcommand));
+            std::process::exit(USAGE_EXIT_CODE);
+        }
+    }
+}
+
+/// Prints a clap error unchanged, but exits with the Go CLI's exit codes. Only
+/// for Go-style invocations; everything else keeps clap's exit code 2 for
+/// usage errors.
+pub fn exit_with_clap_error(err: &clap::Error) -> ! {
+    let _ = err.print();
+    std::process::exit(if err.use_stderr() { USAGE_EXIT_CODE } else { 0 });
+}
+
+/// Formats the options of `cmd` like Go's `flag.PrintDefaults`.
+pub fn go_usage(name: &str, cmd: &Command) -> String {
+    let mut flags: Vec<(String, &Arg)> = cmd
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .filter_map(|arg| Some((go_name(arg.get_long()?), arg)))
+        .filter(|(name, _)| name != "help" && name != "version")
+        .collect();
+    flags.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut usage = format!("Usage of {name}:\n");
+    for (name, arg) in flags {
+        let mut line = format!("  -{name}");
+        if takes_value(arg)
+            && let Some(value_name) = arg.get_value_names().and_then(|names| names.first())
+        {
+            line.push(' ');
+            line.push_str(value_name);
+        }
+        let help = arg.get_help().map(ToString::to_string).unwrap_or_default();
+        usage.push_str(&line);
+        usage.push_str("\n    \t");
+        usage.push_str(&help.replace('\n', "\n    \t"));
+        usage.push('\n');
+    }
+    usage
+}
+
+// The one-line message the Go `flag` package prints before the usage.
+fn go_error(err: &clap::Error) -> String {
+    let invalid_arg = match err.get(ContextKind::InvalidArg) {
+        Some(ContextValue::String(arg)) => Some(arg.as_str()),
+        _ => None,
+    };
+    match (err.kind(), invalid_arg) {
+        (ErrorKind::UnknownArgument, Some(arg)) => {
+            let name = arg.trim_start_matches('-');
+            let name = name.split_once('=').map_or(name, |(name, _)| name);
+            format!("flag provided but not defined: -{name}")
+        }
+        (ErrorKind::InvalidValue | ErrorKind::ValueValidation, Some(arg)) => {
+            let long = arg.trim_start_matches('-');
+            let long = long.split_once([' ', '=']).map_or(long, |(long, _)| long);
+            let value = match err.get(ContextKind::InvalidValue) {
+                Some(ContextValue::String(value)) => value.as_str(),
+                _ => "",
+            };
+            if value.is_empty() && err.kind() == ErrorKind::InvalidValue {
+                return format!("flag needs an argument: -{}", go_name(long));
+            }
+            let reason = err
+                .source()
+                .map_or_else(|| "parse error".to_string(), ToString::to_string);
+            format!(
+                "invalid value {value:?} for flag -{}: {reason}",
+                go_name(long)
+            )
+        }
+        _ => {
+            let rendered = err.to_string();
+            let first = rendered.lines().next().unwrap_or_default();
+            first.strip_prefix("error: ").unwrap_or(first).to_string()
+        }
+    }
+}
+
+// Follows the subcommand names in `args` and returns the command they select
+// together with the name the Go CLI gives its flag set, e.g. "fetch x509".
+fn resolve_subcommand<'a>(cmd: &'a Command, args: &[OsString]) -> (String, &'a Command) {
+    let mut current = cmd;
+    let mut path = Vec::new();
+    let mut skip_value = false;
+    for arg in args.iter().skip(1) {
+        let Some(text) = arg.to_str() else { break };
+        if skip_value {
+            skip_value = false;
+            continue;
+        }
+        if text == "--" {
+            break;
+        }
+        if let Some(long) = text.strip_prefix("--") {
+            skip_value = !long.contains('=')
+                && current
+                    .get_arguments()
+                    .any(|arg| arg.get_long() == Some(long) && takes_value(arg));
+            continue;
+        }
+        match current.find_subcommand(text) {
+            Some(subcommand) => {
+                path.push(subcommand.get_name().to_string());
+                current = subcommand;
+            }
+            None => break,
+        }
+    }
+    // `api` only groups the Workload API commands; Go names the flag sets
+    // after the command that follows it.
+    if path.len() > 1 && path[0] == "api" {
+        path.remove(0);
+    }
+    let name = if path.is_empty() {
+        cmd.get_name().to_string()
+    } else {
+        path.join(" ")
+    };
+    (name, current)
+}
+
+// Every long option (and alias) of a built command, including the global
+// options of its parents, and whether it takes a value.
+fn known_flags(cmd: &Command) -> BTreeMap<String, bool> {
+    let mut flags = BTreeMap::new();
+    for arg in cmd.get_arguments() {
+        let takes = takes_value(arg);
+        for long in arg
+            .get_long()
+            .into_iter()
+            .chain(arg.get_all_aliases().into_iter().flatten())
+        {
+            flags.insert(long.to_string(), takes);
+            flags.insert(long_name(long), takes);
+        }
+    }
+    flags
+}
+
+fn takes_value(arg: &Arg) -> bool {
+    !matches!(
+        arg.get_action(),
+        ArgAction::SetTrue
+            | ArgAction::SetFalse
+            | ArgAction::Count
+            | ArgAction::Help
+            | ArgAction::HelpShort
+            | ArgAction::HelpLong
+            | ArgAction::Version
+    )
+}
+
+// `socketPath` -> `socket-path`, `spiffeID` -> `spiffe-id`; kebab-case names
+// are returned unchanged.
+fn long_name(name: &str) -> String {
+    let mut long = String::with_capacity(name.len() + 2);
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && prev_lower {
+            long.push('-');
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        long.push(c.to_ascii_lowercase());
+    }
+    long
+}
+
+// `socket-path` -> `socketPath`, `spiffe-id` -> `spiffeID`.
+fn go_name(long: &str) -> String {
+    let mut words = long.split('-');
+    let mut name = words.next().unwrap_or_default().to_string();
+    for word in words {
+        if word == "id" {
+            name.push_str("ID");
+            continue;
+        }
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    name
+}
+
+// strconv.ParseBool
+fn parse_go_bool(value: &str) -> Option<bool> {
+    match value {
+        "1" | "t" | "T" | "true" | "TRUE" | "True" => Some(true),
+        "0" | "f" | "F" | "false" | "FALSE" | "False" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use clap::{Arg, ArgAction, Command};
+
+    use super::{go_name, go_usage, long_name, normalize_args};
+
+    fn command() -> Command {
+        Command::new("spire-agent").subcommand(
+            Command::new("api")
+                .arg(
+                    Arg::new("socket_path")
+                        .long("socket-path")
+                        .value_name("string")
+                        .global(true)
+                        .help("Path to the SPIRE Agent API socket"),
+                )
+                .arg(
+                    Arg::new("silent")
+                        .long("silent")
+                        .action(ArgAction::SetTrue)
+                        .global(true)
+                        .help("Suppress stdout"),
+                )
+                .subcommand(
+                    Command::new("fetch").subcommand(
+                        Command::new("jwt").arg(
+                            Arg::new("spiffe_id")
+                                .long("spiffe-id")
+                                .value_name("string")
+                                .help("SPIFFE ID subject (optional)"),
+                        ),
+                    ),
+                ),
+        )
+    }
+
+    fn normalize(args: &[&str]) -> (Vec<String>, bool) {
+        let normalized = normalize_args(&command(), args.iter().map(OsString::from));
+        let args = normalized
+            .args
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect();
+        (args, normalized.go_style)
+    }
+
+    #[test]
+    fn normalize_args_rewrites_go_spellings() {
+        let (args, go_style) = normalize(&[
+            "spire-agent",
+            "api",
+            "fetch",
+            "jwt",
+            "-socketPath",
+            "/tmp/agent.sock",
+            "-spiffeID=spiffe://example.org/w",
+            "-silent=false",
+        ]);
+        assert!(go_style);
+        assert_eq!(
+            args,
+            [
+                "spire-agent",
+                "api",
+                "fetch",
+                "jwt",
+                "--socket-path",
+                "/tmp/agent.sock",
+                "--spiffe-id=spiffe://example.org/w",
+            ]
+        );
+
+        // Values are never rewritten, nor is anything after `--`.
+        let (args, go_style) = normalize(&[
+            "spire-agent",
+            "api",
+            "--socket-path",
+            "-silent",
+            "--silent",
+            "--",
+            "-socketPath",
+        ]);
+        assert!(!go_style);
+        assert_eq!(
+            args,
+            [
+                "spire-agent",
+                "api",
+                "--socket-path",
+                "-silent",
+                "--silent",
+                "--",
+                "-socketPath",
+            ]
+        );
+    }
+
+    #[test]
+    fn normalize_args_only_knows_the_selected_subcommand_flags() {
+        // `-spiffeID` belongs to `fetch jwt`, so `api` leaves it for clap to
+        // reject instead of quietly accepting it.
+        let (args, go_style) = normalize(&["spire-agent", "api", "-spiffeID=x", "fetch", "jwt"]);
+        assert!(go_style);
+        assert_eq!(args, ["spire-agent", "api", "--spiffeID=x", "fetch", "jwt"]);
+
+        let (args, _) = normalize(&["spire-agent", "api", "fetch", "jwt", "-spiffeID=x"]);
+        assert_eq!(
+            args,
+            ["spire-agent", "api", "fetch", "jwt", "--spiffe-id=x"]
+        );
+    }
+
+    #[test]
+    fn names_round_trip_between_go_and_clap() {
+        assert_eq!(long_name("socketPath"), "socket-path");
+        assert_eq!(long_name("spiffeID"), "spiffe-id");
+        assert_eq!(long_name("timeout"), "timeout");
+        assert_eq!(go_name("socket-path"), "socketPath");
+        assert_eq!(go_name("spiffe-id"), "spiffeID");
+    }
+
+    #[test]
+    fn go_usage_matches_flag_print_defaults() {
+        let mut cmd = command();
+        cmd.build();
+        let jwt = cmd
+            .find_subcommand("api")
+            .and_then(|api| api.find_subcommand("fetch"))
+            .and_then(|fetch| fetch.find_subcommand("jwt"))
+            .unwrap();
+        assert_eq!(
+            go_usage("fetch jwt", jwt),
+            "Usage of fetch jwt:\n  \
+             -silent\n    \tSuppress stdout\n  \
+             -socketPath string\n    \tPath to the SPIRE Agent API socket\n  \
+             -spiffeID string\n    \tSPIFFE ID subject (optional)\n"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use spire_agent::backoff::{
+    Backoff, INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY, RECONNECT_JITTER,
+};
+use spire_agent::jwt::refresh_delay;
+use spire_agent::{JwtSvid, WorkloadApiClient, X509Context};
+use tokio::signal::unix::{SignalKind, signal};
+
+use crate::atomic_write::FileSet;
+use crate::fetch_jwt::fetch_jwt_svids;
+use crate::fetch_x509::{add_svids, format_duration_seconds, format_utc_time};
+use crate::notify::Notifier;
+use crate::retry::{Reconnect, RetryPolicy};
+
+/// Options for `api daemon`.
+#[derive(Debug, Default)]
+pub struct DaemonOptions {
+    /// Directory receiving the same files as `api fetch x509 --write`.
+    pub write_dir: String,
+    /// When non-empty, JWT-SVIDs for these audiences are kept fresh as well.
+    pub jwt_audience: Vec<String>,
+    /// Command and signal delivered after every write.
+    pub notifier: Notifier,
+    /// Stop after the first successful write (init containers).
+    pub exit_after_write: bool,
+}
+
+/// Keeps the SVID files in `write_dir` up to date until interrupted or
+/// terminated. `retry` decides whether a failure before the first update
+/// ends the daemon.
+pub async fn run_daemon(
+    socket_path: &str,
+    timeout: Duration,
+    retry: &RetryPolicy,
+    silent: bool,
+    options: &DaemonOptions,
+) -> Result<()> {
+    // Container runtimes and init systems stop the daemon with SIGTERM.
+    let mut terminate =
+        signal(SignalKind::terminate()).context("failed to listen for terminate signal")?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            result.context("failed to listen for interrupt signal")
+        }
+        _ = terminate.recv() => Ok(()),
+        result = daemon_loop(socket_path, timeout, retry, silent, options) => result,
+    }
+}
+
+async fn daemon_loop(
+    socket_path: &str,
+    timeout: Duration,
+    retry: &RetryPolicy,
+    silent: bool,
+    options: &DaemonOptions,
+) -> Result<()> {
+    let mut reconnect = Reconnect::new(retry);
+
+    loop {
+        match stream_updates(socket_path, timeout, silent, options, &mut reconnect).await {
+            Ok(true) => return Ok(()),
+            Ok(false) => eprintln!("Workload API stream closed by the agent"),
+            Err(e) if reconnect.gives_up_on(&e) => return Err(e),
+            Err(e) => eprintln!("Error: {e:#}"),
+        }
+
+        let delay = reconnect.next_delay();
+        eprintln!("Reconnecting in {}", format_duration_seconds(delay));
+        tokio::time::sleep(delay).await;
+    }
+}
+
+// Writes every X.509 update, refreshes JWT-SVIDs at half their lifetime, and
+// notifies the workload after each write. Returns `true` once the daemon is
+// done (`--exit-after-write`) and `false` when the agent closed the stream.
+async fn stream_updates(
+    socket_path: &str,
+    timeout: Duration,
+    silent: bool,
+    options: &DaemonOptions,
+    reconnect: &mut Reconnect,
+) -> Result<bool> {
+    let mut last_update = Instant::now();
+    let mut client = tokio::time::timeout(timeout, WorkloadApiClient::connect(socket_path))
+        .await
+        .context("timed out connecting to the agent")??;
+    let mut stream = tokio::time::timeout(timeout, client.watch_x509_contexts())
+        .await
+        .context("request timed out")??;
+
+    // Every write publishes the X.509 and JWT files together, so the latest
+    // of each is kept for the other's next update.
+    let mut context: Option<X509Context> = None;
+    let mut jwt_svids: Vec<JwtSvid> = Vec::new();
+    let mut jwt_refresh: Option<tokio::time::Instant> = None;
+    let mut jwt_backoff =
+        Backoff::new(INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY).with_jitter(RECONNECT_JITTER);
+    loop {
+        let refresh = async move {
+            match jwt_refresh {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            update = stream.next() => {
+                let Some(update) = update? else {
+                    return Ok(false);
+                };
+                reconnect.connected();
+                if !silent {
+                    println!(
+                        "[{}] Received {} svid after {}",
+                        format_utc_time(Utc::now()),
+                        update.svids().len(),
+                        format_duration_seconds(last_update.elapsed())
+                    );
+                }
+                last_update = Instant::now();
+                context = Some(update);
+
+                // The X.509 files are written even when the JWT-SVIDs
+                // cannot be fetched along with them.
+                if !options.jwt_audience.is_empty() {
+                    let (_, next) = refresh_jwt_svids(
+                        &mut client,
+                        timeout,
+                        options,
+                        &mut jwt_svids,
+                        &mut jwt_backoff,
+                    )
+                    .await;
+                    jwt_refresh = Some(tokio::time::Instant::now() + next);
+                }
+            }
+            () = refresh => {
+                let (refreshed, next) = refresh_jwt_svids(
+                    &mut client,
+                    timeout,
+                    options,
+                    &mut jwt_svids,
+                    &mut jwt_backoff,
+                )
+                .await;
+                jwt_refresh = Some(tokio::time::Instant::now() + next);
+                if !refreshed {
+                    continue;
+                }
+            }
+        }
+
+        // JWT refreshes are only scheduled after the first X.509 update.
+        if let Some(context) = &context {
+            write_files(context, &jwt_svids, silent, options)?;
+        }
+
+        // A failed reload is reported but does not stop the daemon; the
+        // files themselves are already in place.
+        if let Err(e) = options.notifier.notify(silent).await {
+            eprintln!("Error: failed to notify workload: {e:#}");
+        }
+        if options.exit_after_write {
+            return Ok(true);
+        }
+    }
+}
+
+// Replaces `svids` with freshly fetched JWT-SVIDs and returns whether that
+// worked, with how long to wait before the next refresh. A failure only
+// affects the JWT-SVIDs: it is reported, the last tokens are kept, and the
+// refresh is retried with backoff while the X.509 stream stays open.
+async fn refresh_jwt_svids(
+    client: &mut WorkloadApiClient,
+    timeout: Duration,
+    options: &DaemonOptions,
+    svids: &mut Vec<JwtSvid>,
+    backoff: &mut Backoff,
+) -> (bool, Duration) {
+    match fetch_jwt_svids(client, timeout, &options.jwt_audience, None).await {
+        Ok(fetched) => {
+            backoff.reset();
+            let lifetime = fetched.iter().filter_map(JwtSvid::remaining_lifetime).min();
+            *svids = fetched;
+            (true, refresh_delay(lifetime))
+        }
+        Err(e) => {
+            eprintln!("Error: failed to fetch JWT-SVIDs: {e:#}");
+            (false, backoff.next_delay())
+        }
+    }
+}
+
+// Writes the X.509 files and a `jwt_svid.N.token` for every JWT-SVID as one
+// generation.
+fn write_files(
+    context: &X509Context,
+    jwt_svids: &[JwtSvid],
+    silent: bool,
+    options: &DaemonOptions,
+) -> Result<()> {
+    let dir = Path::new(&options.write_dir);
+    let mut files = FileSet::new("daemon");
+    add_svids(&mut files, context, dir, silent)?;
+
+    for (idx, svid) in jwt_svids.iter().enumerate() {
+        let name = format!("jwt_svid.{idx}.token");
+        if !silent {
+            println!(
+                "Writing JWT SVID #{} to file {}.",
+                idx,
+                dir.join(&name).display()
+            );
+        }
+        files.add(name, svid.token().to_string(), 0o600);
+    }
+
+    files.commit(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use spire_agent::grpc::X509svidResponse;
+    use spire_agent::testing::{DEFAULT_SPIFFE_ID, start_agent, x509_svid};
+    use tokio::sync::mpsc;
+    use tonic::Code;
+
+    use super::{DaemonOptions, run_daemon};
+    use crate::retry::RetryPolicy;
+
+    #[tokio::test]
+    async fn run_daemon_writes_x509_and_jwt_files_and_exits() {
+        let dir = std::env::temp_dir().join(format!("spire-agent-daemon-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let agent = start_agent(Duration::from_secs(600)).await;
+        let (updates_tx, updates) = mpsc::channel(4);
+        agent.x509_streams.send(updates).await.unwrap();
+        updates_tx
+            .send(Ok(X509svidResponse {
+                svids: vec![x509_svid(DEFAULT_SPIFFE_ID, "")],
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        let options = DaemonOptions {
+            write_dir: dir.to_str().unwrap().to_string(),
+            jwt_audience: vec!["db".to_string()],
+            exit_after_write: true,
+            ..Default::default()
+        };
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            run_daemon(
+                &agent.address,
+                Duration::from_secs(5),
+                &RetryPolicy::default(),
+                true,
+                &options,
+            ),
+        )
+        .await
+        .expect("daemon did not exit after the first write")
+        .unwrap();
+
+        for name in [
+            "svid.0.pem",
+            "svid.0.key",
+            "bundle.0.pem",
+            "jwt_svid.0.token",
+        ] {
+            assert!(dir.join(name).is_file(), "{name} was not written");
+        }
+        assert_eq!(agent.jwt_svid_calls(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn run_daemon_writes_x509_files_when_jwt_svids_fail() {
+        let dir =
+            std::env::temp_dir().join(format!("spire-agent-daemon-jwt-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let agent = start_agent(Duration::from_secs(600)).await;
+        agent.fail_jwt_svids(Some(Code::PermissionDenied));
+        let (updates_tx, updates) = mpsc::channel(4);
+        agent.x509_streams.send(updates).await.unwrap();
+        updates_tx
+            .send(Ok(X509svidResponse {
+                svids: vec![x509_svid(DEFAULT_SPIFFE_ID, "")],
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+
+        let options = DaemonOptions {
+            write_dir: dir.to_str().unwrap().to_string(),
+            jwt_audience: vec!["rejected".to_string()],
+            exit_after_write: true,
+            ..Default::default()
+        };
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            run_daemon(
+                &agent.address,
+                Duration::from_secs(5),
+                &RetryPolicy::default(),
+                true,
+                &options,
+            ),
+        )
+        .await
+        .expect("daemon did not exit after the first write")
+        .unwrap();
+
+        assert!(dir.join("svid.0.pem").is_file());
+        assert!(!dir.join("jwt_svid.0.token").exists());
+        assert_eq!(agent.jwt_svid_calls(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
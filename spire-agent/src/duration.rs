@@ -0,0 +1,263 @@
+use anyhow::Result;
+
+const NANOSECOND: u64 = 1;
+const MICROSECOND: u64 = 1_000 * NANOSECOND;
+const MILLISECOND: u64 = 1_000 * MICROSECOND;
+const SECOND: u64 = 1_000 * MILLISECOND;
+const MINUTE: u64 = 60 * SECOND;
+const HOUR: u64 = 60 * MINUTE;
+
+// Magnitude of i64::MIN; the largest value a negative duration may reach.
+const LIMIT: u64 = 1 << 63;
+
+const UNITS: &[(&str, u64)] = &[
+    ("ns", NANOSECOND),
+    ("us", MICROSECOND),
+    ("µs", MICROSECOND), // U+00B5 micro sign
+    ("μs", MICROSECOND), // U+03BC Greek small letter mu
+    ("ms", MILLISECOND),
+    ("s", SECOND),
+    ("m", MINUTE),
+    ("h", HOUR),
+];
+
+/// Parses a duration the way Go's `time.ParseDuration` does and returns it in
+/// nanoseconds.
+///
+/// A duration is an optionally signed sequence of decimal numbers, each with
+/// an optional fraction and a unit suffix, such as `300ms`, `-1.5h` or
+/// `2h45m`. Valid units are `ns`, `us` (or `µs`), `ms`, `s`, `m` and `h`.
+/// Only `0` may omit the unit, and the total must fit in an `i64`.
+pub fn parse_go_duration(s: &str) -> Result<i64> {
+    let orig = s;
+    let invalid = || anyhow::anyhow!("invalid duration {orig:?}");
+
+    let (neg, mut s) = match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    if s == "0" {
+        return Ok(0);
+    }
+    if s.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut total: u64 = 0;
+    while !s.is_empty() {
+        if !s.starts_with(|c: char| c == '.' || c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let (value, rest) = leading_int(s).ok_or_else(invalid)?;
+        let has_int = rest.len() != s.len();
+        s = rest;
+
+        let mut fraction = (0, 1.0);
+        let mut has_fraction = false;
+        if let Some(rest) = s.strip_prefix('.') {
+            let (f, scale, after) = leading_fraction(rest);
+            has_fraction = after.len() != rest.len();
+            fraction = (f, scale);
+            s = after;
+        }
+        if !has_int && !has_fraction {
+            return Err(invalid());
+        }
+
+        let unit_len = s
+            .find(|c: char| c == '.' || c.is_ascii_digit())
+            .unwrap_or(s.len());
+        if unit_len == 0 {
+            anyhow::bail!("missing unit in duration {orig:?}");
+        }
+        let (unit, rest) = s.split_at(unit_len);
+        s = rest;
+        let Some(&(_, unit)) = UNITS.iter().find(|(name, _)| *name == unit) else {
+            anyhow::bail!("unknown unit {unit:?} in duration {orig:?}");
+        };
+
+        if value > LIMIT / unit {
+            return Err(invalid());
+        }
+        let mut value = value * unit;
+        let (f, scale) = fraction;
+        if f > 0 {
+            // Like Go, go through float64 so the result matches bit for bit.
+            value += (f as f64 * (unit as f64 / scale)) as u64;
+            if value > LIMIT {
+                return Err(invalid());
+            }
+        }
+        total += value;
+        if total > LIMIT {
+            return Err(invalid());
+        }
+    }
+
+    if neg {
+        return Ok((total as i64).wrapping_neg());
+    }
+    if total > LIMIT - 1 {
+        return Err(invalid());
+    }
+    Ok(total as i64)
+}
+
+// Consumes leading digits; `None` when they overflow the duration range.
+fn leading_int(s: &str) -> Option<(u64, &str)> {
+    let digits = s.bytes().take_while(u8::is_ascii_digit).count();
+    let mut x: u64 = 0;
+    for c in s[..digits].bytes() {
+        if x > LIMIT / 10 {
+            return None;
+        }
+        x = x * 10 + u64::from(c - b'0');
+        if x > LIMIT {
+            return None;
+        }
+    }
+    Some((x, &s[digits..]))
+}
+
+// Consumes the digits after a decimal point, returning them as an integer and
+// the power of ten to divide it by. Digits past the representable precision
+// are skipped rather than treated as an error.
+fn leading_fraction(s: &str) -> (u64, f64, &str) {
+    let digits = s.bytes().take_while(u8::is_ascii_digit).count();
+    let mut x: u64 = 0;
+    let mut scale = 1.0;
+    let mut overflow = false;
+    for c in s[..digits].bytes() {
+        if overflow {
+            continue;
+        }
+        if x > (LIMIT - 1) / 10 {
+            overflow = true;
+            continue;
+        }
+        let y = x * 10 + u64::from(c - b'0');
+        if y > LIMIT {
+            overflow = true;
+            continue;
+        }
+        x = y;
+        scale *= 10.0;
+    }
+    (x, scale, &s[digits..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HOUR, MICROSECOND, MILLISECOND, MINUTE, NANOSECOND, SECOND, parse_go_duration};
+
+    const NS: i64 = NANOSECOND as i64;
+    const US: i64 = MICROSECOND as i64;
+    const MS: i64 = MILLISECOND as i64;
+    const S: i64 = SECOND as i64;
+    const M: i64 = MINUTE as i64;
+    const H: i64 = HOUR as i64;
+
+    // parseDurationTests from Go's src/time/time_test.go.
+    #[test]
+    fn parse_go_duration_matches_go() {
+        let cases: &[(&str, i64)] = &[
+            // simple
+            ("0", 0),
+            ("5s", 5 * S),
+            ("30s", 30 * S),
+            ("1478s", 1478 * S),
+            // sign
+            ("-5s", -5 * S),
+            ("+5s", 5 * S),
+            ("-0", 0),
+            ("+0", 0),
+            // decimal
+            ("5.0s", 5 * S),
+            ("5.6s", 5 * S + 600 * MS),
+            ("5.s", 5 * S),
+            (".5s", 500 * MS),
+            ("1.0s", S),
+            ("1.00s", S),
+            ("1.004s", S + 4 * MS),
+            ("1.0040s", S + 4 * MS),
+            ("100.00100s", 100 * S + MS),
+            // different units
+            ("10ns", 10 * NS),
+            ("11us", 11 * US),
+            ("12µs", 12 * US),
+            ("12μs", 12 * US),
+            ("13ms", 13 * MS),
+            ("14s", 14 * S),
+            ("15m", 15 * M),
+            ("16h", 16 * H),
+            // composite durations
+            ("3h30m", 3 * H + 30 * M),
+            ("10.5s4m", 4 * M + 10 * S + 500 * MS),
+            ("-2m3.4s", -(2 * M + 3 * S + 400 * MS)),
+            (
+                "1h2m3s4ms5us6ns",
+                H + 2 * M + 3 * S + 4 * MS + 5 * US + 6 * NS,
+            ),
+            ("39h9m14.425s", 39 * H + 9 * M + 14 * S + 425 * MS),
+            // large value
+            ("52763797000ns", 52763797000 * NS),
+            // more than 9 digits after decimal point
+            ("0.3333333333333333333h", 20 * M),
+            // 9007199254740993 = 1<<53+1 cannot be stored precisely in a float64
+            ("9007199254740993ns", (1 << 53) + 1),
+            // largest duration that can be represented by int64 in nanoseconds
+            ("9223372036854775807ns", i64::MAX),
+            ("9223372036854775.807us", i64::MAX),
+            ("9223372036854ms775us807ns", i64::MAX),
+            ("-9223372036854775808ns", i64::MIN),
+            ("-9223372036854775.808us", i64::MIN),
+            ("-9223372036854ms775us808ns", i64::MIN),
+            ("-2562047h47m16.854775808s", i64::MIN),
+            // huge string; issue 15011
+            ("0.100000000000000000000h", 6 * M),
+            // this value tests the first overflow check in leadingFraction
+            ("0.830103483285477580700h", 49 * M + 48 * S + 372539827 * NS),
+        ];
+        for &(input, want) in cases {
+            match parse_go_duration(input) {
+                Ok(got) => assert_eq!(got, want, "parse_go_duration({input:?})"),
+                Err(err) => panic!("parse_go_duration({input:?}) failed: {err}"),
+            }
+        }
+    }
+
+    // parseDurationErrorTests and the invalid inputs from parseDurationTests.
+    #[test]
+    fn parse_go_duration_rejects_what_go_rejects() {
+        let cases: &[(&str, &str)] = &[
+            ("", r#""""#),
+            ("3", r#""3""#),
+            ("-", r#""-""#),
+            ("s", r#""s""#),
+            (".", r#"".""#),
+            ("-.", r#""-.""#),
+            (".s", r#"".s""#),
+            ("+.s", r#""+.s""#),
+            ("1d", r#""1d""#),
+            ("1.5x", r#""x""#),
+            ("9223372036854775808ns", "9223372036854775808ns"),
+            ("9223372036854775.808us", "9223372036854775.808us"),
+            ("9223372036854ms775us808ns", "9223372036854ms775us808ns"),
+            ("-9223372036854775809ns", "-9223372036854775809ns"),
+        ];
+        for &(input, mentions) in cases {
+            let err = parse_go_duration(input).expect_err(input).to_string();
+            assert!(err.contains(mentions), "error for {input:?} was {err:?}");
+        }
+        assert_eq!(
+            parse_go_duration("3").unwrap_err().to_string(),
+            r#"missing unit in duration "3""#
+        );
+        assert_eq!(
+            parse_go_duration("1d").unwrap_err().to_string(),
+            r#"unknown unit "d" in duration "1d""#
+        );
+    }
+}
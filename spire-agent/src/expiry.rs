@@ -0,0 +1,215 @@
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use spire_agent::{SpiffeId, X509Bundle, X509Context, X509Svid};
+use x509_cert::Certificate;
+
+use crate::fetch_x509::{format_utc_time, parse_certs, parse_x509_time, svid_bundle};
+
+/// Exit code used when `--min-ttl` finds a certificate close to expiry, so
+/// monitoring scripts can tell it apart from a failed fetch (exit code 1), a
+/// usage error (clap's 2) and the healthcheck failure codes (3 to 8).
+pub const MIN_TTL_EXIT_CODE: i32 = 9;
+
+/// A certificate whose remaining lifetime is below the requested minimum.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiringCert {
+    pub spiffe_id: SpiffeId,
+    pub role: String,
+    pub subject: String,
+    pub not_after: DateTime<Utc>,
+    pub remaining: Option<Duration>,
+}
+
+impl fmt::Display for ExpiringCert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let remaining = match self.remaining {
+            Some(remaining) => format!("expires in {}", format_ttl(remaining)),
+            None => "has expired".to_string(),
+        };
+        write!(
+            f,
+            "SVID {} {} ({}) {} (not after {})",
+            self.spiffe_id,
+            self.role,
+            self.subject,
+            remaining,
+            format_utc_time(self.not_after)
+        )
+    }
+}
+
+/// Returned by `api fetch x509 --min-ttl` when any certificate expires within
+/// the threshold. `commands::run` maps it to `MIN_TTL_EXIT_CODE`.
+#[derive(Debug)]
+pub struct MinTtlError {
+    pub min_ttl: Duration,
+    pub expiring: Vec<ExpiringCert>,
+}
+
+impl fmt::Display for MinTtlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} certificate(s) expire within {}",
+            self.expiring.len(),
+            format_ttl(self.min_ttl)
+        )?;
+        for cert in &self.expiring {
+            write!(f, "\n  {cert}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MinTtlError {}
+
+/// Fails with `MinTtlError` if a leaf, intermediate or bundle CA of any SVID
+/// expires less than `min_ttl` after `now`.
+pub fn check_min_ttl(context: &X509Context, min_ttl: Duration, now: DateTime<Utc>) -> Result<()> {
+    let mut expiring = Vec::new();
+    for svid in context.svids() {
+        let bundle = svid_bundle(context, svid)?;
+        expiring.extend(expiring_certs(svid, bundle, min_ttl, now)?);
+    }
+
+    if expiring.is_empty() {
+        Ok(())
+    } else {
+        Err(MinTtlError { min_ttl, expiring }.into())
+    }
+}
+
+fn expiring_certs(
+    svid: &X509Svid,
+    bundle: &X509Bundle,
+    min_ttl: Duration,
+    now: DateTime<Utc>,
+) -> Result<Vec<ExpiringCert>> {
+    let chain = parse_certs(svid.cert_chain())?;
+    let bundle = parse_certs(bundle.authorities())?;
+
+    let mut certs: Vec<(String, &Certificate)> = Vec::new();
+    if let Some((leaf, intermediates)) = chain.split_first() {
+        certs.push(("leaf".to_string(), leaf));
+        certs.extend(
+            (1..)
+                .zip(intermediates)
+                .map(|(num, cert)| (format!("intermediate #{num}"), cert)),
+        );
+    }
+    certs.extend(
+        (1..)
+            .zip(&bundle)
+            .map(|(num, cert)| (format!("bundle CA #{num}"), cert)),
+    );
+
+    let expiring = certs
+        .into_iter()
+        .filter_map(|(role, cert)| {
+            let tbs = &cert.tbs_certificate;
+            let not_after = parse_x509_time(&tbs.validity.not_after);
+            let remaining = (not_after - now).to_std().ok();
+            if remaining.is_some_and(|remaining| remaining >= min_ttl) {
+                return None;
+            }
+            Some(ExpiringCert {
+                spiffe_id: svid.spiffe_id().clone(),
+                role,
+                subject: tbs.subject.to_string(),
+                not_after,
+                remaining,
+            })
+        })
+        .collect();
+
+    Ok(expiring)
+}
+
+// Renders whole seconds the way Go's `time.Duration` does, e.g. `1h2m3s`.
+fn format_ttl(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes}m{seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m{seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+    use rcgen::date_time_ymd;
+
+    use spire_agent::{X509BundleSet, X509Context, X509Svid};
+
+    use super::{MinTtlError, check_min_ttl, format_ttl};
+    use spire_agent::testing::{TestCa, ca_params, leaf_params};
+
+    fn context() -> X509Context {
+        let mut ca_params = ca_params("root", None);
+        ca_params.not_before = date_time_ymd(2024, 1, 1);
+        ca_params.not_after = date_time_ymd(2034, 1, 1);
+        let ca = TestCa::from_params(ca_params);
+
+        let mut leaf_params = leaf_params("spiffe://example.org/w");
+        leaf_params.not_before = date_time_ymd(2025, 1, 1);
+        leaf_params.not_after = date_time_ymd(2025, 1, 2);
+        let leaf = ca.issue(leaf_params);
+
+        let spiffe_id = "spiffe://example.org/w".parse().unwrap();
+        let svid = X509Svid::new(spiffe_id, vec![leaf.der], leaf.key).unwrap();
+        let mut bundles = X509BundleSet::new();
+        bundles.insert(ca.bundle("example.org"));
+        X509Context::new(vec![svid], bundles)
+    }
+
+    #[test]
+    fn check_min_ttl_reports_only_certificates_below_threshold() {
+        let context = context();
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap();
+
+        check_min_ttl(&context, Duration::from_secs(3600), now).expect("leaf has 12h left");
+
+        let err = check_min_ttl(&context, Duration::from_secs(24 * 3600), now)
+            .expect_err("leaf has less than 24h left");
+        let err = err.downcast_ref::<MinTtlError>().expect("MinTtlError");
+        assert_eq!(err.expiring.len(), 1);
+        assert_eq!(err.expiring[0].spiffe_id, "spiffe://example.org/w");
+        assert_eq!(err.expiring[0].role, "leaf");
+        assert_eq!(
+            err.expiring[0].remaining,
+            Some(Duration::from_secs(12 * 3600))
+        );
+    }
+
+    #[test]
+    fn check_min_ttl_reports_expired_certificates() {
+        let context = context();
+        let now = Utc.with_ymd_and_hms(2033, 12, 31, 0, 0, 0).unwrap();
+
+        let err = check_min_ttl(&context, Duration::from_secs(48 * 3600), now).expect_err("expired");
+        let err = err.downcast_ref::<MinTtlError>().expect("MinTtlError");
+        let roles: Vec<_> = err.expiring.iter().map(|cert| cert.role.as_str()).collect();
+        assert_eq!(roles, ["leaf", "bundle CA #1"]);
+        assert_eq!(err.expiring[0].remaining, None);
+        assert_eq!(
+            err.expiring[1].remaining,
+            Some(Duration::from_secs(24 * 3600))
+        );
+    }
+
+    #[test]
+    fn format_ttl_matches_go_durations() {
+        assert_eq!(format_ttl(Duration::ZERO), "0s");
+        assert_eq!(format_ttl(Duration::from_secs(600)), "10m0s");
+        assert_eq!(format_ttl(Duration::from_secs(3723)), "1h2m3s");
+    }
+}
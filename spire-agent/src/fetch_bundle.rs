@@ -0,0 +1,130 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Serialize;
+use spire_agent::{WorkloadApiClient, X509BundleSet};
+
+use crate::atomic_write::FileSet;
+use crate::fetch_x509::{
+    bundles_json, format_duration_seconds, format_utc_time, parse_certs, parse_x509_time,
+    pem_certs, print_crls, trust_domain_file_stem,
+};
+use crate::output::{OutputFormat, print_json};
+use crate::retry::{RetryPolicy, retry};
+
+pub async fn fetch_bundle(
+    socket_path: &str,
+    timeout: Duration,
+    retry_policy: &RetryPolicy,
+    silent: bool,
+    write_dir: Option<&str>,
+    output: OutputFormat,
+) -> Result<()> {
+    let start = Instant::now();
+    let bundles = retry(retry_policy, || async {
+        let mut client = WorkloadApiClient::connect(socket_path).await?;
+        fetch_x509_bundles(&mut client, timeout).await
+    })
+    .await?;
+
+    let elapsed = start.elapsed();
+    if !silent {
+        match output {
+            OutputFormat::Pretty => print_bundles(&bundles, elapsed)?,
+            OutputFormat::Json => print_json(&X509BundlesResponseJson::from(&bundles))?,
+        }
+    }
+    if let Some(dir) = write_dir {
+        let quiet = silent || output == OutputFormat::Json;
+        write_bundles(&bundles, dir, quiet)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn fetch_x509_bundles(
+    client: &mut WorkloadApiClient,
+    timeout: Duration,
+) -> Result<X509BundleSet> {
+    tokio::time::timeout(timeout, client.fetch_x509_bundles())
+        .await
+        .context("request timed out")?
+}
+
+fn print_bundles(bundles: &X509BundleSet, elapsed: Duration) -> Result<()> {
+    println!(
+        "Received {} bundle after {}\n",
+        bundles.len(),
+        format_duration_seconds(elapsed)
+    );
+
+    for bundle in bundles.iter() {
+        println!("Trust domain:\t\t{}", bundle.trust_domain().id());
+
+        let certs = parse_certs(bundle.authorities())?;
+        for (ca_num, cert) in (1..).zip(&certs) {
+            let tbs = &cert.tbs_certificate;
+            println!("CA #{} Subject:\t\t{}", ca_num, tbs.subject);
+            println!(
+                "CA #{} Valid After:\t{}",
+                ca_num,
+                format_utc_time(parse_x509_time(&tbs.validity.not_before))
+            );
+            println!(
+                "CA #{} Valid Until:\t{}",
+                ca_num,
+                format_utc_time(parse_x509_time(&tbs.validity.not_after))
+            );
+        }
+        println!();
+    }
+    print_crls(bundles.crls())?;
+
+    Ok(())
+}
+
+fn write_bundles(bundles: &X509BundleSet, write_dir: &str, silent: bool) -> Result<()> {
+    let dir = Path::new(write_dir);
+    let mut files = FileSet::new("bundle");
+
+    for bundle in bundles.iter() {
+        let bundle_name = format!(
+            "bundle.{}.pem",
+            trust_domain_file_stem(bundle.trust_domain().name())
+        );
+
+        if !silent {
+            println!(
+                "Writing bundle for trust domain {} to file {}.",
+                bundle.trust_domain().id(),
+                dir.join(&bundle_name).display()
+            );
+        }
+        files.add(bundle_name, pem_certs(bundle.authorities())?, 0o644);
+    }
+
+    files.commit(dir)
+}
+
+// JSON rendering of a bundle set as `X509BundlesResponse`, in the layout used by
+// `api fetch x509 --output json`.
+#[derive(Debug, Serialize)]
+struct X509BundlesResponseJson {
+    crl: Vec<String>,
+    bundles: BTreeMap<String, String>,
+}
+
+impl From<&X509BundleSet> for X509BundlesResponseJson {
+    fn from(bundles: &X509BundleSet) -> Self {
+        Self {
+            crl: bundles.crls().iter().map(|crl| BASE64.encode(crl)).collect(),
+            bundles: bundles_json(bundles.iter()),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt::Write;
+use std::{collections::BTreeMap, time::Duration};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Serialize;
+use spire_agent::{JwtBundleSet, JwtSvid, SpiffeId, WorkloadApiClient};
+
+use crate::fetch_x509::format_utc_time;
+use crate::output::{OutputFormat, print_json};
+use crate::retry::{RetryPolicy, retry};
+
+pub async fn fetch_jwt(
+    socket_path: &str,
+    timeout: Duration,
+    retry_policy: &RetryPolicy,
+    silent: bool,
+    output: OutputFormat,
+    audience: &[String],
+    spiffe_id: Option<&SpiffeId>,
+) -> Result<()> {
+    if audience.is_empty() {
+        anyhow::bail!("audience must be specified");
+    }
+
+    let (svids, bundles) = retry(retry_policy, || async {
+        let mut client = WorkloadApiClient::connect(socket_path).await?;
+        let svids = fetch_jwt_svids(&mut client, timeout, audience, spiffe_id).await?;
+        let bundles = fetch_jwt_bundles(&mut client, timeout).await?;
+        Ok((svids, bundles))
+    })
+    .await?;
+
+    if silent {
+        return Ok(());
+    }
+    match output {
+        OutputFormat::Pretty => print_jwt(&svids, &bundles),
+        OutputFormat::Json => print_json(&(
+            JwtSvidResponseJson::from(svids.as_slice()),
+            JwtBundlesResponseJson::from(&bundles),
+        )),
+    }
+}
+
+pub(crate) async fn fetch_jwt_svids(
+    client: &mut WorkloadApiClient,
+    timeout: Duration,
+    audience: &[String],
+    spiffe_id: Option<&SpiffeId>,
+) -> Result<Vec<JwtSvid>> {
+    tokio::time::timeout(timeout, client.fetch_jwt_svids(audience, spiffe_id))
+        .await
+        .context("request timed out")?
+}
+
+pub(crate) async fn fetch_jwt_bundles(
+    client: &mut WorkloadApiClient,
+    timeout: Duration,
+) -> Result<JwtBundleSet> {
+    tokio::time::timeout(timeout, client.fetch_jwt_bundles())
+        .await
+        .context("request timed out")?
+}
+
+fn print_jwt(svids: &[JwtSvid], bundles: &JwtBundleSet) -> Result<()> {
+    print!("{}", format_jwt(svids, bundles)?);
+    Ok(())
+}
+
+fn format_jwt(svids: &[JwtSvid], bundles: &JwtBundleSet) -> Result<String> {
+    let mut out = String::new();
+    for svid in svids {
+        writeln!(out, "token({}):\n\t{}", svid.spiffe_id(), svid.token())?;
+
+        let claims = serde_json::to_string(svid.claims()).context("failed to encode claims")?;
+        writeln!(out, "claims({}):\n\t{}", svid.spiffe_id(), claims)?;
+        if let Some(expiry) = svid.expiry() {
+            writeln!(
+                out,
+                "expires({}):\n\t{}",
+                svid.spiffe_id(),
+                format_utc_time(expiry)
+            )?;
+        }
+    }
+
+    for bundle in bundles.iter() {
+        writeln!(
+            out,
+            "bundle({}):\n\t{}",
+            bundle.trust_domain().id(),
+            String::from_utf8_lossy(bundle.jwks())
+        )?;
+    }
+
+    Ok(out)
+}
+
+// JSON rendering of JWT-SVIDs and bundles as `JWTSVIDResponse` and
+// `JWTBundlesResponse`, laid out the way the Go agent's protojson output is.
+#[derive(Debug, Serialize)]
+struct JwtSvidResponseJson {
+    svids: Vec<JwtSvidJson>,
+}
+
+#[derive(Debug, Serialize)]
+struct JwtSvidJson {
+    spiffe_id: String,
+    svid: String,
+    hint: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JwtBundlesResponseJson {
+    bundles: BTreeMap<String, String>,
+}
+
+impl From<&[JwtSvid]> for JwtSvidResponseJson {
+    fn from(svids: &[JwtSvid]) -> Self {
+        Self {
+            svids: svids
+                .iter()
+                .map(|svid| JwtSvidJson {
+                    spiffe_id: svid.spiffe_id().to_string(),
+                    svid: svid.token().to_string(),
+                    hint: svid.hint().to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&JwtBundleSet> for JwtBundlesResponseJson {
+    fn from(bundles: &JwtBundleSet) -> Self {
+        Self {
+            bundles: bundles
+                .iter()
+                .map(|bundle| {
+                    (
+                        bundle.trust_domain().id().to_string(),
+                        BASE64.encode(bundle.jwks()),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use spire_agent::testing::{DEFAULT_SPIFFE_ID, start_agent};
+    use spire_agent::{JwtBundle, JwtBundleSet, JwtSvid, WorkloadApiClient};
+
+    use super::{
+        JwtBundlesResponseJson, JwtSvidResponseJson, fetch_jwt, fetch_jwt_svids, format_jwt,
+    };
+    use crate::output::OutputFormat;
+    use crate::retry::RetryPolicy;
+
+    // {"alg":"ES256"}.{"aud":["a"],"exp":1700000000,"sub":"spiffe://example.org/w"}.sig
+    const TOKEN: &str = "eyJhbGciOiJFUzI1NiJ9.\
+        eyJhdWQiOlsiYSJdLCJleHAiOjE3MDAwMDAwMDAsInN1YiI6InNwaWZmZTovL2V4YW1wbGUub3JnL3cifQ.\
+        c2ln";
+
+    fn svids_and_bundles() -> (Vec<JwtSvid>, JwtBundleSet) {
+        let svid = JwtSvid::parse("spiffe://example.org/w".parse().unwrap(), TOKEN)
+            .unwrap()
+            .with_hint("internal");
+        let mut bundles = JwtBundleSet::new();
+        bundles.insert(JwtBundle::new(
+            "example.org".parse().unwrap(),
+            br#"{"keys":[]}"#.to_vec(),
+        ));
+        (vec![svid], bundles)
+    }
+
+    #[test]
+    fn format_jwt_prints_tokens_claims_and_bundles() {
+        let (svids, bundles) = svids_and_bundles();
+        assert_eq!(
+            format_jwt(&svids, &bundles).unwrap(),
+            format!(
+                concat!(
+                    "token(spiffe://example.org/w):\n\t{}\n",
+                    "claims(spiffe://example.org/w):\n",
+                    "\t{{\"aud\":[\"a\"],\"exp\":1700000000,\"sub\":\"spiffe://example.org/w\"}}\n",
+                    "expires(spiffe://example.org/w):\n\t2023-11-14 22:13:20 +0000 UTC\n",
+                    "bundle(spiffe://example.org):\n\t{{\"keys\":[]}}\n",
+                ),
+                TOKEN
+            )
+        );
+    }
+
+    #[test]
+    fn jwt_json_matches_protojson_layout() {
+        let (svids, bundles) = svids_and_bundles();
+        let json = serde_json::to_string(&(
+            JwtSvidResponseJson::from(svids.as_slice()),
+            JwtBundlesResponseJson::from(&bundles),
+        ))
+        .unwrap();
+        assert_eq!(
+            json,
+            format!(
+                concat!(
+                    r#"[{{"svids":[{{"spiffe_id":"spiffe://example.org/w","svid":"{}","#,
+                    r#""hint":"internal"}}]}},"#,
+                    r#"{{"bundles":{{"spiffe://example.org":"eyJrZXlzIjpbXX0="}}}}]"#,
+                ),
+                TOKEN
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_jwt_requires_an_audience() {
+        let err = fetch_jwt(
+            "unix:///nonexistent",
+            Duration::from_secs(1),
+            &RetryPolicy::default(),
+            true,
+            OutputFormat::Pretty,
+            &[],
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "audience must be specified");
+    }
+
+    #[tokio::test]
+    async fn fetch_jwt_svids_requests_every_audience() {
+        let agent = start_agent(Duration::from_secs(600)).await;
+        let mut client = WorkloadApiClient::connect(&agent.address).await.unwrap();
+        let audience = vec!["a".to_string(), "b".to_string()];
+
+        let svids = fetch_jwt_svids(&mut client, Duration::from_secs(5), &audience, None)
+            .await
+            .unwrap();
+        assert_eq!(svids.len(), 1);
+        assert_eq!(svids[0].spiffe_id(), DEFAULT_SPIFFE_ID);
+        assert_eq!(svids[0].audience(), ["a", "b"]);
+
+        let other = "spiffe://example.org/other".parse().unwrap();
+        let svids = fetch_jwt_svids(&mut client, Duration::from_secs(5), &audience, Some(&other))
+            .await
+            .unwrap();
+        assert_eq!(svids[0].spiffe_id(), &other);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use spire_agent::{JwtBundle, TrustDomain, WorkloadApiClient, X509Bundle};
+
+use crate::atomic_write::FileSet;
+use crate::fetch_bundle::fetch_x509_bundles;
+use crate::fetch_jwt::fetch_jwt_bundles;
+use crate::fetch_x509::{parse_certs, trust_domain_file_stem};
+use crate::jwk::{parse_jwks, spiffe_bundle};
+use crate::output::{OutputFormat, print_json};
+use crate::retry::{RetryPolicy, retry};
+
+pub async fn fetch_jwt_bundle(
+    socket_path: &str,
+    timeout: Duration,
+    retry_policy: &RetryPolicy,
+    silent: bool,
+    write_dir: Option<&str>,
+    output: OutputFormat,
+    write_spiffe_bundle: bool,
+) -> Result<()> {
+    if write_spiffe_bundle && write_dir.is_none() {
+        anyhow::bail!("--spiffe-bundle requires --write");
+    }
+
+    // The X.509 bundles are only needed for --spiffe-bundle, but are fetched
+    // up front so that both come from the same retried connection.
+    let (jwt_bundles, x509_bundles) = retry(retry_policy, || async {
+        let mut client = WorkloadApiClient::connect(socket_path).await?;
+        let jwt_bundles = fetch_jwt_bundles(&mut client, timeout).await?;
+        let x509_bundles = if write_spiffe_bundle {
+            Some(fetch_x509_bundles(&mut client, timeout).await?)
+        } else {
+            None
+        };
+        Ok((jwt_bundles, x509_bundles))
+    })
+    .await?;
+
+    let mut bundles = BTreeMap::new();
+    for bundle in jwt_bundles.iter() {
+        let trust_domain = bundle.trust_domain().id().to_string();
+        let jwks = parse_jwks(bundle.jwks())
+            .with_context(|| format!("invalid JWT bundle for {trust_domain}"))?;
+        bundles.insert(trust_domain, jwks);
+    }
+
+    if !silent {
+        match output {
+            OutputFormat::Pretty => print_jwks(&bundles)?,
+            OutputFormat::Json => print_json(&bundles)?,
+        }
+    }
+
+    let Some(dir) = write_dir else {
+        return Ok(());
+    };
+    let quiet = silent || output == OutputFormat::Json;
+    let dir = Path::new(dir);
+    let mut files = FileSet::new("jwt_bundle");
+    for (trust_domain, jwks) in &bundles {
+        let name = format!("jwt_bundle.{}.json", trust_domain_file_stem(trust_domain));
+        if !quiet {
+            println!(
+                "Writing JWT bundle for trust domain {} to file {}.",
+                trust_domain,
+                dir.join(&name).display()
+            );
+        }
+        files.add(name, json_file(&Value::Object(jwks.clone()))?, 0o644);
+    }
+
+    if let Some(x509_bundles) = x509_bundles {
+        let trust_domains: BTreeSet<&TrustDomain> = jwt_bundles
+            .iter()
+            .map(JwtBundle::trust_domain)
+            .chain(x509_bundles.iter().map(X509Bundle::trust_domain))
+            .collect();
+
+        for trust_domain in trust_domains {
+            let trust_domain_id = trust_domain.id().to_string();
+            let authorities = match x509_bundles.get(trust_domain) {
+                Some(bundle) => parse_certs(bundle.authorities())?,
+                None => Vec::new(),
+            };
+            let document = spiffe_bundle(&authorities, bundles.get(&trust_domain_id))
+                .with_context(|| format!("failed to build SPIFFE bundle for {trust_domain_id}"))?;
+
+            let name = format!(
+                "spiffe_bundle.{}.json",
+                trust_domain_file_stem(trust_domain.name())
+            );
+            if !quiet {
+                println!(
+                    "Writing SPIFFE bundle for trust domain {} to file {}.",
+                    trust_domain_id,
+                    dir.join(&name).display()
+                );
+            }
+            files.add(name, json_file(&document)?, 0o644);
+        }
+    }
+
+    files.commit(dir)
+}
+
+fn print_jwks(bundles: &BTreeMap<String, Map<String, Value>>) -> Result<()> {
+    for (trust_domain, jwks) in bundles {
+        let jwks = serde_json::to_string(jwks).context("failed to encode JWKS")?;
+        println!("bundle({trust_domain}):\n\t{jwks}");
+    }
+    Ok(())
+}
+
+fn json_file(value: &Value) -> Result<String> {
+    let mut contents = serde_json::to_string_pretty(value).context("failed to encode JSON")?;
+    contents.push('\n');
+    Ok(contents)
+}
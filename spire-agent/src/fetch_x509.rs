@@ -1,116 +1,358 @@
 use std::{
-    fs,
-    path::Path,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use chrono::{DateTime, Utc};
-use der::{Encode, Reader};
+use der::Decode;
 use pem_rfc7468::LineEnding;
+use serde::Serialize;
+use spire_agent::grpc::{X509svid, X509svidResponse};
+use spire_agent::{WorkloadApiClient, X509Bundle, X509Context, X509Svid};
 use x509_cert::Certificate;
-
-use crate::rpc::{WorkloadClient, connect_workload_client};
-use crate::grpc::{
-    X509svid, X509svidRequest, X509svidResponse,
-};
+use x509_cert::crl::CertificateList;
+
+use crate::atomic_write::FileSet;
+use crate::expiry::check_min_ttl;
+use crate::inspect::inspect_svid;
+use crate::output::{OutputFormat, WriteFormat, print_json};
+use crate::pkcs12::{PASSPHRASE_ENV, resolve_passphrase, svid_keystore, truststore};
+use crate::retry::{RetryPolicy, retry};
+use crate::verify::verify_svids;
+
+/// Options specific to `api fetch x509`.
+#[derive(Debug, Default)]
+pub struct FetchX509Options {
+    /// Print certificate details for every SVID (pretty output only).
+    pub inspect: bool,
+    /// Verify each SVID before printing its verification report.
+    pub verify: bool,
+    /// Fail with `MinTtlError` when a certificate expires within this window.
+    pub min_ttl: Option<Duration>,
+    /// File format for `--write`.
+    pub write_format: WriteFormat,
+    /// PKCS#12 passphrase from `--passphrase`.
+    pub passphrase: Option<String>,
+    /// File holding the PKCS#12 passphrase.
+    pub passphrase_file: Option<PathBuf>,
+}
 
 pub async fn fetch_x509(
     socket_path: &str,
     timeout: Duration,
+    retry_policy: &RetryPolicy,
     silent: bool,
     write_dir: Option<&str>,
+    output: OutputFormat,
+    options: &FetchX509Options,
 ) -> Result<()> {
+    let passphrase = match options.write_format {
+        WriteFormat::Pem => None,
+        WriteFormat::Pkcs12 if write_dir.is_none() => {
+            anyhow::bail!("--format pkcs12 requires --write")
+        }
+        WriteFormat::Pkcs12 => Some(resolve_passphrase(
+            options.passphrase.as_deref(),
+            options.passphrase_file.as_deref(),
+            std::env::var(PASSPHRASE_ENV).ok().as_deref(),
+        )?),
+    };
+
     let start = Instant::now();
-    let mut client = connect_workload_client(socket_path).await?;
-    let resp = fetch_x509svid(&mut client, timeout).await?;
+    let response = retry(retry_policy, || async {
+        let mut client = WorkloadApiClient::connect(socket_path).await?;
+        fetch_x509_response(&mut client, timeout).await
+    })
+    .await?;
+    let context = X509Context::try_from(&response)?;
 
     let elapsed = start.elapsed();
-    let svids = resp.svids;
     if !silent {
-        print_svids(&svids, elapsed)?;
+        match output {
+            OutputFormat::Pretty => print_svids(&context, elapsed, options.inspect)?,
+            OutputFormat::Json => print_json(&X509SvidResponseJson::from(&response))?,
+        }
+    }
+    if options.verify {
+        // Refuse to write material that failed verification.
+        verify_svids(&context, silent || output == OutputFormat::Json)?;
     }
     if let Some(dir) = write_dir {
-        write_svids(&svids, dir, silent)?;
+        // Keep stdout parseable when it carries JSON.
+        let quiet = silent || output == OutputFormat::Json;
+        match &passphrase {
+            None => write_svids(&context, dir, quiet)?,
+            Some(passphrase) => write_svids_pkcs12(&context, dir, passphrase, quiet)?,
+        }
+    }
+    if let Some(min_ttl) = options.min_ttl {
+        // Checked last so the SVIDs are still written while they remain valid.
+        check_min_ttl(&context, min_ttl, Utc::now())?;
     }
 
     Ok(())
 }
 
-async fn fetch_x509svid(
-    client: &mut WorkloadClient,
+// JSON rendering of `X509SVIDResponse`. Field names and encodings follow
+// protojson with `UseProtoNames` and `EmitUnpopulated`, which is what the Go
+// agent emits for `-output json`: bytes fields are standard base64 and every
+// field is present even when empty. It is rendered from the message as
+// received rather than from an `X509Context`, which merges the SVIDs' bundles
+// into its bundle set and so cannot reproduce the message.
+#[derive(Debug, Serialize)]
+pub(crate) struct X509SvidResponseJson {
+    svids: Vec<X509SvidJson>,
+    crl: Vec<String>,
+    federated_bundles: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+struct X509SvidJson {
+    spiffe_id: String,
+    x509_svid: String,
+    x509_svid_key: String,
+    bundle: String,
+    hint: String,
+}
+
+impl From<&X509svidResponse> for X509SvidResponseJson {
+    fn from(resp: &X509svidResponse) -> Self {
+        Self {
+            svids: resp.svids.iter().map(X509SvidJson::from).collect(),
+            crl: resp.crl.iter().map(|crl| BASE64.encode(crl)).collect(),
+            federated_bundles: resp
+                .federated_bundles
+                .iter()
+                .map(|(trust_domain, bundle)| (trust_domain.clone(), BASE64.encode(bundle)))
+                .collect(),
+        }
+    }
+}
+
+impl From<&X509svid> for X509SvidJson {
+    fn from(svid: &X509svid) -> Self {
+        Self {
+            spiffe_id: svid.spiffe_id.clone(),
+            x509_svid: BASE64.encode(&svid.x509_svid),
+            x509_svid_key: BASE64.encode(&svid.x509_svid_key),
+            bundle: BASE64.encode(&svid.bundle),
+            hint: svid.hint.clone(),
+        }
+    }
+}
+
+// Bundle maps keyed by trust domain ID, as the Workload API sends them.
+pub(crate) fn bundles_json<'a>(
+    bundles: impl Iterator<Item = &'a X509Bundle>,
+) -> BTreeMap<String, String> {
+    bundles
+        .map(|bundle| {
+            (
+                bundle.trust_domain().id().to_string(),
+                BASE64.encode(bundle.authorities().concat()),
+            )
+        })
+        .collect()
+}
+
+async fn fetch_x509_response(
+    client: &mut WorkloadApiClient,
     timeout: Duration,
 ) -> Result<X509svidResponse> {
-    let request = tonic::Request::new(X509svidRequest {});
-    let response = tokio::time::timeout(timeout, client.fetch_x509svid(request))
+    let fetch = async {
+        client
+            .watch_x509_svid_responses()
+            .await?
+            .next()
+            .await?
+            .context("empty response from server")
+    };
+    tokio::time::timeout(timeout, fetch)
         .await
         .context("request timed out")?
-        .context("failed to fetch x509 svid")?;
-
-    let mut stream = response.into_inner();
-    let resp = tokio::time::timeout(timeout, stream.message())
-        .await
-        .context("timed out waiting for response")?
-        .context("failed to receive message")?
-        .context("empty response from server")?;
-
-    Ok(resp)
 }
 
-fn print_svids(svids: &[X509svid], elapsed: Duration) -> Result<()> {
+fn print_svids(context: &X509Context, elapsed: Duration, inspect: bool) -> Result<()> {
     println!(
         "Received {} svid after {}\n",
-        svids.len(),
+        context.svids().len(),
         format_duration_seconds(elapsed)
     );
 
-    for svid in svids {
-        print_svid(svid)?;
+    for svid in context.svids() {
+        let bundle = svid_bundle(context, svid)?;
+        print_svid(svid, bundle)?;
+        if inspect {
+            println!();
+            inspect_svid(svid, bundle)?;
+        }
     }
+    print_federated_bundles(context.federated_bundles())?;
+    print_crls(context.bundles().crls())?;
 
     Ok(())
 }
 
-fn write_svids(svids: &[X509svid], write_dir: &str, silent: bool) -> Result<()> {
+// The agent sends every SVID together with its trust domain's bundle, so this
+// only fails for contexts built by hand.
+pub(crate) fn svid_bundle<'a>(context: &'a X509Context, svid: &X509Svid) -> Result<&'a X509Bundle> {
+    context
+        .bundle_for(svid)
+        .with_context(|| format!("no bundle for trust domain {}", svid.trust_domain()))
+}
+
+pub(crate) fn write_svids(context: &X509Context, write_dir: &str, silent: bool) -> Result<()> {
     let dir = Path::new(write_dir);
-    if dir.exists() && !dir.is_dir() {
-        anyhow::bail!("write path is not a directory: {}", dir.display());
+    let mut files = FileSet::new("x509");
+    add_svids(&mut files, context, dir, silent)?;
+    files.commit(dir)
+}
+
+// Queues the PEM files `--write` produces for `context`.
+pub(crate) fn add_svids(
+    files: &mut FileSet,
+    context: &X509Context,
+    dir: &Path,
+    silent: bool,
+) -> Result<()> {
+    for (idx, svid) in context.svids().iter().enumerate() {
+        let svid_name = format!("svid.{idx}.pem");
+        let key_name = format!("svid.{idx}.key");
+        let bundle_name = format!("bundle.{idx}.pem");
+
+        if !silent {
+            println!("Writing SVID #{} to file {}.", idx, dir.join(&svid_name).display());
+        }
+        files.add(svid_name, pem_certs(svid.cert_chain())?, 0o644);
+
+        if !silent {
+            println!("Writing key #{} to file {}.", idx, dir.join(&key_name).display());
+        }
+        files.add(key_name, pem_key(svid.private_key())?, 0o600);
+
+        if !silent {
+            println!("Writing bundle #{} to file {}.", idx, dir.join(&bundle_name).display());
+        }
+        files.add(bundle_name, pem_certs(svid_bundle(context, svid)?.authorities())?, 0o644);
     }
-    fs::create_dir_all(dir).context("failed to create output directory")?;
 
-    for (idx, svid) in svids.iter().enumerate() {
-        let svid_path = dir.join(format!("svid.{idx}.pem"));
-        let key_path = dir.join(format!("svid.{idx}.key"));
-        let bundle_path = dir.join(format!("bundle.{idx}.pem"));
+    for bundle in context.federated_bundles() {
+        let bundle_name = format!(
+            "federated_bundle.{}.pem",
+            trust_domain_file_stem(bundle.trust_domain().name())
+        );
+
+        if !silent {
+            println!(
+                "Writing federated bundle for trust domain {} to file {}.",
+                bundle.trust_domain().id(),
+                dir.join(&bundle_name).display()
+            );
+        }
+        files.add(bundle_name, pem_certs(bundle.authorities())?, 0o644);
+    }
+
+    add_crls(files, context.bundles().crls(), dir, silent)
+}
+
+// Writes one keystore per SVID (key, chain and its bundle as trusted entries)
+// and a truststore-only file per bundle; CRLs have no PKCS#12 form and stay PEM.
+fn write_svids_pkcs12(
+    context: &X509Context,
+    write_dir: &str,
+    passphrase: &str,
+    silent: bool,
+) -> Result<()> {
+    let dir = Path::new(write_dir);
+    let mut files = FileSet::new("x509");
+
+    for (idx, svid) in context.svids().iter().enumerate() {
+        let bundle = svid_bundle(context, svid)?;
+        let keystore_name = format!("svid.{idx}.p12");
+        let truststore_name = format!("bundle.{idx}.p12");
 
-        let svid_pem = pem_cert_chain(&svid.x509_svid)?;
-        write_pem_file(&svid_path, &svid_pem, Some(0o644))?;
         if !silent {
-            println!("Writing SVID #{} to file {}.", idx, svid_path.display());
+            println!(
+                "Writing SVID #{} keystore to file {}.",
+                idx,
+                dir.join(&keystore_name).display()
+            );
         }
+        files.add(keystore_name, svid_keystore(svid, bundle, passphrase)?, 0o600);
+
+        if !silent {
+            println!(
+                "Writing bundle #{} truststore to file {}.",
+                idx,
+                dir.join(&truststore_name).display()
+            );
+        }
+        files.add(truststore_name, truststore(bundle, passphrase)?, 0o644);
+    }
+
+    for bundle in context.federated_bundles() {
+        let bundle_name = format!(
+            "federated_bundle.{}.p12",
+            trust_domain_file_stem(bundle.trust_domain().name())
+        );
 
-        let key_pem = pem_key(&svid.x509_svid_key)?;
-        write_pem_file(&key_path, &key_pem, Some(0o600))?;
         if !silent {
-            println!("Writing key #{} to file {}.", idx, key_path.display());
+            println!(
+                "Writing federated bundle truststore for trust domain {} to file {}.",
+                bundle.trust_domain().id(),
+                dir.join(&bundle_name).display()
+            );
         }
+        files.add(bundle_name, truststore(bundle, passphrase)?, 0o644);
+    }
+
+    add_crls(&mut files, context.bundles().crls(), dir, silent)?;
+    files.commit(dir)
+}
+
+fn add_crls(files: &mut FileSet, crls: &[Vec<u8>], dir: &Path, silent: bool) -> Result<()> {
+    for (idx, crl) in crls.iter().enumerate() {
+        let crl_name = format!("crl.{idx}.pem");
 
-        let bundle_pem = pem_cert_chain(&svid.bundle)?;
-        write_pem_file(&bundle_path, &bundle_pem, Some(0o644))?;
         if !silent {
-            println!("Writing bundle #{} to file {}.", idx, bundle_path.display());
+            println!("Writing CRL #{} to file {}.", idx, dir.join(&crl_name).display());
         }
+        files.add(crl_name, pem_single("X509 CRL", crl)?, 0o644);
     }
 
     Ok(())
 }
 
-fn pem_cert_chain(der_bytes: &[u8]) -> Result<String> {
-    let certs = parse_cert_chain(der_bytes)?;
+// Turns a trust domain (with or without the `spiffe://` scheme) into a string
+// that is safe to embed in a file name: anything outside the trust domain
+// character set, including path separators, becomes `_`.
+pub(crate) fn trust_domain_file_stem(trust_domain: &str) -> String {
+    let name = trust_domain
+        .strip_prefix("spiffe://")
+        .unwrap_or(trust_domain);
+    let stem: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+
+    if stem.is_empty() || stem.chars().all(|c| c == '.') {
+        stem.replace('.', "_") + "_"
+    } else {
+        stem
+    }
+}
+
+pub(crate) fn pem_certs(certs: &[Vec<u8>]) -> Result<String> {
     let mut pem = String::new();
-    for cert in certs {
-        let der = cert.to_der().context("failed to encode certificate")?;
-        pem.push_str(&pem_single("CERTIFICATE", &der)?);
+    for der in certs {
+        pem.push_str(&pem_single("CERTIFICATE", der)?);
     }
     Ok(pem)
 }
@@ -124,47 +366,76 @@ fn pem_key(der_bytes: &[u8]) -> Result<String> {
     pem_single("PRIVATE KEY", der_bytes)
 }
 
-fn write_pem_file(path: &Path, contents: &str, mode: Option<u32>) -> Result<()> {
-    #[cfg(unix)]
-    {
-        use std::io::Write;
-        use std::os::unix::fs::OpenOptionsExt;
-
-        let file_mode = mode.unwrap_or(0o644);
-        let mut file = std::fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .mode(file_mode)
-            .open(path)
-            .with_context(|| format!("failed to open {}", path.display()))?;
-        file.write_all(contents.as_bytes())
-            .with_context(|| format!("failed to write {}", path.display()))?;
-        Ok(())
-    }
+pub(crate) fn print_svid(svid: &X509Svid, bundle: &X509Bundle) -> Result<()> {
+    println!("SPIFFE ID:\t\t{}", svid.spiffe_id());
 
-    #[cfg(not(unix))] 
-    {
-        fs::write(path, contents.as_bytes()).with_context(|| {
-            format!("failed to write {}", path.display())
-        })?;
-        Ok(())
-    }
-}
-
-fn print_svid(svid: &X509svid) -> Result<()> {
-    println!("SPIFFE ID:\t\t{}", svid.spiffe_id);
-
-    let svid_certs = parse_cert_chain(&svid.x509_svid)?;
+    let svid_certs = parse_certs(svid.cert_chain())?;
     print_leaf_validity(&svid_certs);
     print_intermediate_validity(&svid_certs);
 
-    let bundle_certs = parse_cert_chain(&svid.bundle)?;
+    let bundle_certs = parse_certs(bundle.authorities())?;
     print_bundle_validity(&bundle_certs);
 
     Ok(())
 }
 
+pub(crate) fn print_federated_bundles<'a>(
+    bundles: impl Iterator<Item = &'a X509Bundle>,
+) -> Result<()> {
+    for bundle in bundles {
+        let trust_domain = bundle.trust_domain().id();
+        let certs = parse_certs(bundle.authorities())?;
+        for (ca_num, cert) in (1..).zip(&certs) {
+            let validity = &cert.tbs_certificate.validity;
+            let not_before = parse_x509_time(&validity.not_before);
+            let not_after = parse_x509_time(&validity.not_after);
+
+            println!(
+                "[{}] CA #{} Valid After:\t{}",
+                trust_domain,
+                ca_num,
+                format_utc_time(not_before)
+            );
+            println!(
+                "[{}] CA #{} Valid Until:\t{}",
+                trust_domain,
+                ca_num,
+                format_utc_time(not_after)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn print_crls(crls: &[Vec<u8>]) -> Result<()> {
+    for (crl_num, crl) in (1..).zip(crls) {
+        let crl = CertificateList::from_der(crl).context("failed to parse CRL")?;
+        let tbs = &crl.tbs_cert_list;
+
+        println!("CRL #{} Issuer:\t\t{}", crl_num, tbs.issuer);
+        println!(
+            "CRL #{} This Update:\t{}",
+            crl_num,
+            format_utc_time(parse_x509_time(&tbs.this_update))
+        );
+        if let Some(next_update) = &tbs.next_update {
+            println!(
+                "CRL #{} Next Update:\t{}",
+                crl_num,
+                format_utc_time(parse_x509_time(next_update))
+            );
+        }
+        println!(
+            "CRL #{} Revoked:\t{}",
+            crl_num,
+            tbs.revoked_certificates.as_ref().map_or(0, Vec::len)
+        );
+    }
+
+    Ok(())
+}
+
 fn print_leaf_validity(certs: &[Certificate]) {
     let Some(leaf) = certs.first() else {
         return;
@@ -178,9 +449,7 @@ fn print_leaf_validity(certs: &[Certificate]) {
 }
 
 fn print_intermediate_validity(certs: &[Certificate]) {
-    let mut intermediate_num = 1;
-
-    for cert in certs.iter().skip(1) {
+    for (intermediate_num, cert) in (1..).zip(certs.iter().skip(1)) {
         let validity = &cert.tbs_certificate.validity;
         let not_before = parse_x509_time(&validity.not_before);
         let not_after = parse_x509_time(&validity.not_after);
@@ -197,14 +466,11 @@ fn print_intermediate_validity(certs: &[Certificate]) {
             intermediate_num,
             format_utc_time(not_after)
         );
-        intermediate_num += 1;
     }
 }
 
 fn print_bundle_validity(certs: &[Certificate]) {
-    let mut ca_num = 1;
-
-    for cert in certs {
+    for (ca_num, cert) in (1..).zip(certs) {
         let validity = &cert.tbs_certificate.validity;
         let not_before = parse_x509_time(&validity.not_before);
         let not_after = parse_x509_time(&validity.not_after);
@@ -221,25 +487,17 @@ fn print_bundle_validity(certs: &[Certificate]) {
             ca_num,
             format_utc_time(not_after)
         );
-        ca_num += 1;
     }
 }
 
-fn parse_cert_chain(der_bytes: &[u8]) -> Result<Vec<Certificate>> {
-    let mut certs = Vec::new();
-    let mut reader = der::SliceReader::new(der_bytes).context("failed to create DER reader")?;
-
-    while !reader.is_finished() {
-        let cert = reader
-            .decode::<Certificate>()
-            .context("failed to parse certificate")?;
-        certs.push(cert);
-    }
-
-    Ok(certs)
+pub(crate) fn parse_certs(certs: &[Vec<u8>]) -> Result<Vec<Certificate>> {
+    certs
+        .iter()
+        .map(|der| Certificate::from_der(der).context("failed to parse certificate"))
+        .collect()
 }
 
-fn parse_x509_time(time: &x509_cert::time::Time) -> DateTime<Utc> {
+pub(crate) fn parse_x509_time(time: &x509_cert::time::Time) -> DateTime<Utc> {
     match time {
         x509_cert::time::Time::UtcTime(ut) => {
             let unix = ut.to_unix_duration().as_secs() as i64;
@@ -252,11 +510,11 @@ fn parse_x509_time(time: &x509_cert::time::Time) -> DateTime<Utc> {
     }
 }
 
-fn format_utc_time(time: DateTime<Utc>) -> String {
+pub(crate) fn format_utc_time(time: DateTime<Utc>) -> String {
     time.format("%Y-%m-%d %H:%M:%S +0000 UTC").to_string()
 }
 
-fn format_duration_seconds(duration: Duration) -> String {
+pub(crate) fn format_duration_seconds(duration: Duration) -> String {
     if duration.is_zero() {
         return "0s".to_string();
     }
@@ -277,7 +535,9 @@ fn format_duration_seconds(duration: Duration) -> String {
 mod tests {
     use std::time::Duration;
 
-    use super::{format_duration_seconds, parse_cert_chain};
+    use spire_agent::grpc::{X509svid, X509svidResponse};
+
+    use super::{X509SvidResponseJson, format_duration_seconds, parse_certs, trust_domain_file_stem};
 
     const CERT1_DER: &[u8] = &
         [
@@ -377,13 +637,10 @@ mod tests {
         ];
 
     #[test]
-    fn parse_cert_chain_reads_concatenated_certs() {
-        let mut chain = Vec::with_capacity(CERT1_DER.len() + CERT2_DER.len());
-        chain.extend_from_slice(CERT1_DER);
-        chain.extend_from_slice(CERT2_DER);
-
-        let certs = parse_cert_chain(&chain).expect("valid chain");
+    fn parse_certs_reads_each_certificate() {
+        let certs = parse_certs(&[CERT1_DER.to_vec(), CERT2_DER.to_vec()]).expect("valid certs");
         assert_eq!(certs.len(), 2);
+        assert!(parse_certs(&[vec![0x30, 0x00]]).is_err());
     }
 
     #[test]
@@ -400,4 +657,48 @@ mod tests {
         );
         assert_eq!(format_duration_seconds(Duration::from_secs(60)), "60s");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn x509_response_json_matches_protojson_layout() {
+        // The SVID's own trust domain is also listed as federated, with a
+        // bundle of its own; both are re-emitted exactly as received.
+        let resp = X509svidResponse {
+            svids: vec![X509svid {
+                spiffe_id: "spiffe://example.org/workload".to_string(),
+                x509_svid: vec![0x01, 0x02],
+                x509_svid_key: vec![0x03],
+                bundle: vec![0x04, 0x05, 0x06],
+                hint: String::new(),
+            }],
+            crl: vec![],
+            federated_bundles: [
+                ("spiffe://b.org".to_string(), vec![0xff]),
+                ("spiffe://a.org".to_string(), vec![0xfe]),
+                ("spiffe://example.org".to_string(), vec![0xfd]),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        let json = serde_json::to_string(&X509SvidResponseJson::from(&resp)).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"svids":[{"spiffe_id":"spiffe://example.org/workload","#,
+                r#""x509_svid":"AQI=","x509_svid_key":"Aw==","bundle":"BAUG","hint":""}],"#,
+                r#""crl":[],"federated_bundles":{"spiffe://a.org":"/g==","#,
+                r#""spiffe://b.org":"/w==","spiffe://example.org":"/Q=="}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn trust_domain_file_stem_is_path_safe() {
+        assert_eq!(trust_domain_file_stem("spiffe://example.org"), "example.org");
+        assert_eq!(trust_domain_file_stem("example-1.org"), "example-1.org");
+        assert_eq!(trust_domain_file_stem("spiffe://../etc/passwd"), ".._etc_passwd");
+        assert_eq!(trust_domain_file_stem("spiffe://a/b\\c"), "a_b_c");
+        assert_eq!(trust_domain_file_stem("spiffe://.."), "___");
+        assert_eq!(trust_domain_file_stem("spiffe://"), "_");
+    }
+}
@@ -1,13 +1,204 @@
-use std::time::Duration;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
-use crate::rpc::{connect_channel, connect_workload_client};
-use crate::grpc::X509svidRequest;
+use anyhow::Result;
+use serde::Serialize;
+use spire_agent::WorkloadApiClient;
+use spire_agent::rpc::{WorkloadAddress, connect_channel, health_client};
+use tokio::time::error::Elapsed;
+use tonic::Status;
+use tonic::transport::Channel;
+use tonic_health::pb::HealthCheckRequest;
+use tonic_health::pb::health_check_response::ServingStatus;
 
-const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+use crate::fetch_x509::format_duration_seconds;
+use crate::output::{OutputFormat, print_json};
+use crate::retry::{RetryPolicy, retry};
 
-pub async fn healthcheck(socket_path: &str, shallow: bool, verbose: bool) -> Result<()> {
-    check_workload_health(socket_path, DEFAULT_TIMEOUT).await?;
+/// Why the agent was found unhealthy. Each variant keeps the underlying
+/// error as its source, so `{:#}` prints the full cause.
+#[derive(Debug)]
+pub enum HealthcheckError {
+    /// Nothing exists at the Workload API socket path.
+    SocketMissing {
+        address: String,
+        source: anyhow::Error,
+    },
+    /// The socket exists (or the port is closed) but nobody accepts on it.
+    ConnectionRefused {
+        address: String,
+        source: anyhow::Error,
+    },
+    /// Any other failure to reach the endpoint.
+    ConnectFailed {
+        address: String,
+        source: anyhow::Error,
+    },
+    /// A check did not finish within `--timeout`.
+    TimedOut {
+        check: &'static str,
+        timeout: Duration,
+        source: Elapsed,
+    },
+    /// The `grpc.health.v1.Health/Check` call itself failed.
+    HealthRequestFailed(Status),
+    /// The agent answered, but with a status other than `SERVING`.
+    NotServing(ServingStatus),
+    /// The agent is serving but could not deliver an X509-SVID.
+    SvidFetchFailed(anyhow::Error),
+}
+
+impl fmt::Display for HealthcheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SocketMissing { address, .. } => {
+                write!(f, "Workload API socket {address} does not exist")
+            }
+            Self::ConnectionRefused { address, .. } => {
+                write!(f, "connection to {address} refused; is the agent running?")
+            }
+            Self::ConnectFailed { address, .. } => write!(f, "failed to connect to {address}"),
+            Self::TimedOut { check, timeout, .. } => write!(
+                f,
+                "{check} timed out after {}",
+                format_duration_seconds(*timeout)
+            ),
+            Self::HealthRequestFailed(_) => write!(f, "health check request failed"),
+            Self::NotServing(status) => {
+                write!(f, "agent is not serving (status {})", status.as_str_name())
+            }
+            Self::SvidFetchFailed(_) => write!(f, "X509-SVID check failed"),
+        }
+    }
+}
+
+impl HealthcheckError {
+    /// Short name of the failure class, as reported in JSON output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::SocketMissing { .. } => "socket_missing",
+            Self::ConnectionRefused { .. } => "connection_refused",
+            Self::ConnectFailed { .. } => "connect_failed",
+            Self::TimedOut { .. } => "timed_out",
+            Self::HealthRequestFailed(_) => "health_request_failed",
+            Self::NotServing(_) => "not_serving",
+            Self::SvidFetchFailed(_) => "svid_fetch_failed",
+        }
+    }
+
+    /// Process exit code for the failure class, so probes can tell an agent
+    /// that is not up yet from one that is up but broken. 1 stays the code
+    /// for anything unclassified.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::ConnectFailed { .. } => 1,
+            Self::SocketMissing { .. } => 3,
+            Self::ConnectionRefused { .. } => 4,
+            Self::TimedOut { .. } => 5,
+            Self::HealthRequestFailed(_) => 6,
+            Self::NotServing(_) => 7,
+            Self::SvidFetchFailed(_) => 8,
+        }
+    }
+}
+
+impl std::error::Error for HealthcheckError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::SocketMissing { source, .. }
+            | Self::ConnectionRefused { source, .. }
+            | Self::ConnectFailed { source, .. }
+            | Self::SvidFetchFailed(source) => Some(source.as_ref()),
+            Self::TimedOut { source, .. } => Some(source),
+            Self::HealthRequestFailed(status) => Some(status),
+            Self::NotServing(_) => None,
+        }
+    }
+}
+
+/// One step of the health check, as listed in JSON output.
+#[derive(Debug, Serialize)]
+struct CheckJson {
+    name: &'static str,
+    status: &'static str,
+    latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthcheckJson<'a> {
+    healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    checks: &'a [CheckJson],
+}
+
+pub async fn healthcheck(
+    socket_path: &str,
+    timeout: Duration,
+    retry_policy: &RetryPolicy,
+    output: OutputFormat,
+    shallow: bool,
+    verbose: bool,
+) -> Result<()> {
+    let verbose = verbose && output == OutputFormat::Pretty;
+    // Only the last attempt is reported when retries are enabled.
+    let last_checks = RefCell::new(Vec::new());
+    let result = retry(retry_policy, || async {
+        let mut checks = Vec::new();
+        let result = check_agent(socket_path, timeout, shallow, verbose, &mut checks).await;
+        last_checks.replace(checks);
+        result.map_err(anyhow::Error::from)
+    })
+    .await;
+
+    match output {
+        OutputFormat::Pretty => {
+            if result.is_ok() {
+                if verbose {
+                    println!("Agent health: ok");
+                }
+                println!("Agent is healthy.");
+            }
+        }
+        OutputFormat::Json => print_json(&json_report(&result, &last_checks.borrow()))?,
+    }
+    result
+}
+
+fn json_report<'a>(result: &Result<()>, checks: &'a [CheckJson]) -> HealthcheckJson<'a> {
+    let error = result
+        .as_ref()
+        .err()
+        .and_then(|err| err.downcast_ref::<HealthcheckError>());
+    HealthcheckJson {
+        healthy: result.is_ok(),
+        failure: error.map(HealthcheckError::kind),
+        exit_code: error.map(HealthcheckError::exit_code),
+        checks,
+    }
+}
+
+async fn check_agent(
+    socket_path: &str,
+    timeout: Duration,
+    shallow: bool,
+    verbose: bool,
+    checks: &mut Vec<CheckJson>,
+) -> Result<(), HealthcheckError> {
+    let channel = run_check(checks, "connect", connect(socket_path, timeout)).await?;
+    run_check(
+        checks,
+        "health",
+        check_workload_health(channel.clone(), timeout),
+    )
+    .await?;
     if verbose {
         println!("Workload API health check: ok");
     }
@@ -16,42 +207,239 @@ pub async fn healthcheck(socket_path: &str, shallow: bool, verbose: bool) -> Res
         if verbose {
             println!("Workload API X509-SVID check: starting");
         }
-        check_x509_svid(socket_path, DEFAULT_TIMEOUT).await?;
+        run_check(checks, "x509_svid", check_x509_svid(channel, timeout)).await?;
         if verbose {
             println!("Workload API X509-SVID check: ok");
         }
     }
-
-    if verbose {
-        println!("Agent health: ok");
-    }
-    println!("Agent is healthy.");
     Ok(())
 }
 
-async fn check_workload_health(socket_path: &str, timeout: Duration) -> Result<()> {
-    tokio::time::timeout(timeout, connect_channel(socket_path))
+// Runs one step and records its outcome and latency.
+async fn run_check<T>(
+    checks: &mut Vec<CheckJson>,
+    name: &'static str,
+    check: impl Future<Output = Result<T, HealthcheckError>>,
+) -> Result<T, HealthcheckError> {
+    let start = Instant::now();
+    let result = check.await;
+    checks.push(CheckJson {
+        name,
+        status: if result.is_ok() { "ok" } else { "failed" },
+        latency_ms: start.elapsed().as_micros() as f64 / 1000.0,
+        error: result.as_ref().err().map(ToString::to_string),
+    });
+    result
+}
+
+async fn connect(socket_path: &str, timeout: Duration) -> Result<Channel, HealthcheckError> {
+    let address = WorkloadAddress::parse(socket_path)
+        .map_err(|source| HealthcheckError::ConnectFailed {
+            address: socket_path.to_string(),
+            source,
+        })?
+        .to_string();
+    let result = tokio::time::timeout(timeout, connect_channel(socket_path))
         .await
-        .context("request timed out")?
-        .context("health check request failed")?;
-    Ok(())
+        .map_err(|source| HealthcheckError::TimedOut {
+            check: "connecting to the agent",
+            timeout,
+            source,
+        })?;
+
+    result.map_err(|source| match io_error_kind(&source) {
+        Some(io::ErrorKind::NotFound) => HealthcheckError::SocketMissing { address, source },
+        Some(io::ErrorKind::ConnectionRefused) => {
+            HealthcheckError::ConnectionRefused { address, source }
+        }
+        _ => HealthcheckError::ConnectFailed { address, source },
+    })
 }
 
-async fn check_x509_svid(socket_path: &str, timeout: Duration) -> Result<()> {
-    let mut client = connect_workload_client(socket_path).await?;
-    let request = tonic::Request::new(X509svidRequest {});
+async fn check_workload_health(
+    channel: Channel,
+    timeout: Duration,
+) -> Result<(), HealthcheckError> {
+    let mut client = health_client(channel);
+    // The empty service name asks about the server as a whole.
+    let request = tonic::Request::new(HealthCheckRequest {
+        service: String::new(),
+    });
 
-    let response = tokio::time::timeout(timeout, client.fetch_x509svid(request))
+    let response = tokio::time::timeout(timeout, client.check(request))
         .await
-        .context("request timed out")?
-        .context("failed to fetch x509 svid")?;
+        .map_err(|source| HealthcheckError::TimedOut {
+            check: "health check",
+            timeout,
+            source,
+        })?
+        .map_err(HealthcheckError::HealthRequestFailed)?;
 
-    let mut stream = response.into_inner();
-    tokio::time::timeout(timeout, stream.message())
+    match response.into_inner().status() {
+        ServingStatus::Serving => Ok(()),
+        status => Err(HealthcheckError::NotServing(status)),
+    }
+}
+
+async fn check_x509_svid(channel: Channel, timeout: Duration) -> Result<(), HealthcheckError> {
+    let mut client = WorkloadApiClient::from_channel(channel);
+
+    tokio::time::timeout(timeout, client.fetch_x509_context())
         .await
-        .context("timed out waiting for response")?
-        .context("failed to receive message")?
-        .context("empty response from server")?;
+        .map_err(|source| HealthcheckError::TimedOut {
+            check: "X509-SVID check",
+            timeout,
+            source,
+        })?
+        .map(|_| ())
+        .map_err(HealthcheckError::SvidFetchFailed)
+}
 
-    Ok(())
+fn io_error_kind(err: &anyhow::Error) -> Option<io::ErrorKind> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<io::Error>())
+        .map(io::Error::kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::{Value, json};
+    use tonic::transport::Server;
+    use tonic::transport::server::TcpIncoming;
+    use tonic_health::pb::health_check_response::ServingStatus;
+    use tonic_health::server::HealthReporter;
+
+    use super::{CheckJson, HealthcheckError, check_agent, json_report};
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    // Serves only the health service and returns its reporter and address.
+    async fn serve_health() -> (HealthReporter, String) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("tcp://{}", listener.local_addr().unwrap());
+        let (reporter, health_service) = tonic_health::server::health_reporter();
+        tokio::spawn(
+            Server::builder()
+                .add_service(health_service)
+                .serve_with_incoming(TcpIncoming::from(listener)),
+        );
+        (reporter, address)
+    }
+
+    // The JSON report of a shallow check, without the latencies.
+    async fn shallow_report(address: &str) -> (Value, Option<i32>) {
+        let mut checks: Vec<CheckJson> = Vec::new();
+        let result = check_agent(address, TIMEOUT, true, false, &mut checks).await;
+        let exit_code = result.as_ref().err().map(HealthcheckError::exit_code);
+        let mut report =
+            serde_json::to_value(json_report(&result.map_err(Into::into), &checks)).unwrap();
+        for check in report["checks"].as_array_mut().unwrap() {
+            assert!(check["latency_ms"].is_f64());
+            check.as_object_mut().unwrap().remove("latency_ms");
+        }
+        (report, exit_code)
+    }
+
+    #[tokio::test]
+    async fn check_agent_reports_missing_and_refused_sockets() {
+        let dir = std::env::temp_dir().join(format!("healthcheck-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("agent.sock");
+        let socket_path = socket.to_str().unwrap();
+
+        let err = check_agent(socket_path, TIMEOUT, true, false, &mut Vec::new())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, HealthcheckError::SocketMissing { .. }),
+            "{err}"
+        );
+
+        // A listener that went away leaves its socket file behind.
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        let err = check_agent(socket_path, TIMEOUT, true, false, &mut Vec::new())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, HealthcheckError::ConnectionRefused { .. }),
+            "{err}"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_agent_requires_serving_status() {
+        let (reporter, address) = serve_health().await;
+
+        let mut checks = Vec::new();
+        check_agent(&address, TIMEOUT, true, false, &mut checks)
+            .await
+            .unwrap();
+        let names: Vec<_> = checks
+            .iter()
+            .map(|check| (check.name, check.status))
+            .collect();
+        assert_eq!(names, [("connect", "ok"), ("health", "ok")]);
+
+        reporter
+            .set_service_status("", tonic_health::ServingStatus::NotServing)
+            .await;
+        let mut checks = Vec::new();
+        let err = check_agent(&address, TIMEOUT, true, false, &mut checks)
+            .await
+            .unwrap_err();
+        assert_eq!(err.exit_code(), 7);
+        assert_eq!(checks[1].status, "failed");
+        assert_eq!(
+            checks[1].error.as_deref(),
+            Some("agent is not serving (status NOT_SERVING)")
+        );
+        assert!(
+            matches!(err, HealthcheckError::NotServing(ServingStatus::NotServing)),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn json_report_describes_healthy_and_failing_runs() {
+        let (reporter, address) = serve_health().await;
+
+        let (report, exit_code) = shallow_report(&address).await;
+        assert_eq!(exit_code, None);
+        assert_eq!(
+            report,
+            json!({
+                "healthy": true,
+                "checks": [
+                    { "name": "connect", "status": "ok" },
+                    { "name": "health", "status": "ok" },
+                ],
+            })
+        );
+
+        reporter
+            .set_service_status("", tonic_health::ServingStatus::NotServing)
+            .await;
+        let (report, exit_code) = shallow_report(&address).await;
+        assert_eq!(exit_code, Some(7));
+        assert_eq!(
+            report,
+            json!({
+                "healthy": false,
+                "failure": "not_serving",
+                "exit_code": 7,
+                "checks": [
+                    { "name": "connect", "status": "ok" },
+                    {
+                        "name": "health",
+                        "status": "failed",
+                        "error": "agent is not serving (status NOT_SERVING)",
+                    },
+                ],
+            })
+        );
+    }
 }
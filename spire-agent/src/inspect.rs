@@ -0,0 +1,290 @@
+use std::net::IpAddr;
+
+use anyhow::{Context, Result, anyhow};
+use const_oid::db::rfc5280::{
+    ID_KP_CLIENT_AUTH, ID_KP_CODE_SIGNING, ID_KP_EMAIL_PROTECTION, ID_KP_OCSP_SIGNING,
+    ID_KP_SERVER_AUTH, ID_KP_TIME_STAMPING,
+};
+use const_oid::db::rfc5912::{
+    ID_EC_PUBLIC_KEY, RSA_ENCRYPTION, SECP_256_R_1, SECP_384_R_1, SECP_521_R_1,
+};
+use const_oid::db::rfc8410::ID_ED_25519;
+use const_oid::{AssociatedOid, ObjectIdentifier};
+use der::{Decode, Encode};
+use sha2::{Digest, Sha256};
+use spire_agent::{X509Bundle, X509Svid};
+use x509_cert::Certificate;
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::{BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAltName};
+
+use crate::fetch_x509::{format_utc_time, parse_certs, parse_x509_time};
+use crate::jwk::{ec_curve, parse_rsa_public_key};
+
+/// Everything `api fetch x509 --inspect` reports about a single certificate.
+#[derive(Debug, Default)]
+pub struct CertificateDetails {
+    pub serial_number: String,
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub uri_sans: Vec<String>,
+    pub dns_sans: Vec<String>,
+    pub ip_sans: Vec<String>,
+    pub public_key: String,
+    pub signature_algorithm: String,
+    pub key_usage: Vec<&'static str>,
+    pub extended_key_usage: Vec<String>,
+    pub basic_constraints: Option<String>,
+    pub sha256_fingerprint: String,
+}
+
+pub fn inspect_svid(svid: &X509Svid, bundle: &X509Bundle) -> Result<()> {
+    let svid_certs = parse_certs(svid.cert_chain())?;
+    for (num, cert) in (1..).zip(&svid_certs) {
+        let role = if num == 1 { "leaf" } else { "intermediate" };
+        print_details(&format!("SVID Certificate #{num} ({role})"), cert)?;
+    }
+
+    let bundle_certs = parse_certs(bundle.authorities())?;
+    for (num, cert) in (1..).zip(&bundle_certs) {
+        print_details(&format!("CA #{num}"), cert)?;
+    }
+
+    Ok(())
+}
+
+fn print_details(title: &str, cert: &Certificate) -> Result<()> {
+    let details = certificate_details(cert)?;
+
+    println!("{title}");
+    print_field("Serial Number", &details.serial_number);
+    print_field("Subject", &details.subject);
+    print_field("Issuer", &details.issuer);
+    print_field("Valid After", &details.not_before);
+    print_field("Valid Until", &details.not_after);
+    for uri in &details.uri_sans {
+        print_field("URI SAN", uri);
+    }
+    for dns in &details.dns_sans {
+        print_field("DNS SAN", dns);
+    }
+    for ip in &details.ip_sans {
+        print_field("IP SAN", ip);
+    }
+    print_field("Public Key", &details.public_key);
+    print_field("Signature Algorithm", &details.signature_algorithm);
+    if !details.key_usage.is_empty() {
+        print_field("Key Usage", &details.key_usage.join(", "));
+    }
+    if !details.extended_key_usage.is_empty() {
+        print_field("Extended Key Usage", &details.extended_key_usage.join(", "));
+    }
+    if let Some(basic_constraints) = &details.basic_constraints {
+        print_field("Basic Constraints", basic_constraints);
+    }
+    print_field("SHA-256 Fingerprint", &details.sha256_fingerprint);
+    println!();
+
+    Ok(())
+}
+
+fn print_field(label: &str, value: &str) {
+    println!("  {:<21}{}", format!("{label}:"), value);
+}
+
+pub fn certificate_details(cert: &Certificate) -> Result<CertificateDetails> {
+    let tbs = &cert.tbs_certificate;
+    let mut details = CertificateDetails {
+        serial_number: colon_hex(tbs.serial_number.as_bytes()),
+        subject: tbs.subject.to_string(),
+        issuer: tbs.issuer.to_string(),
+        not_before: format_utc_time(parse_x509_time(&tbs.validity.not_before)),
+        not_after: format_utc_time(parse_x509_time(&tbs.validity.not_after)),
+        public_key: describe_public_key(cert)?,
+        signature_algorithm: oid_name(&cert.signature_algorithm.oid),
+        sha256_fingerprint: colon_hex(&Sha256::digest(
+            cert.to_der().context("failed to encode certificate")?,
+        )),
+        ..Default::default()
+    };
+
+    if let Some(san) = find_extension::<SubjectAltName>(cert)? {
+        for name in san.0 {
+            match name {
+                GeneralName::UniformResourceIdentifier(uri) => {
+                    details.uri_sans.push(uri.to_string())
+                }
+                GeneralName::DnsName(dns) => details.dns_sans.push(dns.to_string()),
+                GeneralName::IpAddress(ip) => details.ip_sans.push(format_ip(ip.as_bytes())),
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(key_usage) = find_extension::<KeyUsage>(cert)? {
+        details.key_usage = [
+            (key_usage.digital_signature(), "Digital Signature"),
+            (key_usage.non_repudiation(), "Non Repudiation"),
+            (key_usage.key_encipherment(), "Key Encipherment"),
+            (key_usage.data_encipherment(), "Data Encipherment"),
+            (key_usage.key_agreement(), "Key Agreement"),
+            (key_usage.key_cert_sign(), "Certificate Sign"),
+            (key_usage.crl_sign(), "CRL Sign"),
+            (key_usage.encipher_only(), "Encipher Only"),
+            (key_usage.decipher_only(), "Decipher Only"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect();
+    }
+
+    if let Some(eku) = find_extension::<ExtendedKeyUsage>(cert)? {
+        details.extended_key_usage = eku.0.iter().map(extended_key_usage_name).collect();
+    }
+
+    if let Some(constraints) = find_extension::<BasicConstraints>(cert)? {
+        details.basic_constraints = Some(match (constraints.ca, constraints.path_len_constraint) {
+            (false, _) => "CA:FALSE".to_string(),
+            (true, None) => "CA:TRUE".to_string(),
+            (true, Some(path_len)) => format!("CA:TRUE, pathlen:{path_len}"),
+        });
+    }
+
+    Ok(details)
+}
+
+pub(crate) fn find_extension<T>(cert: &Certificate) -> Result<Option<T>>
+where
+    T: AssociatedOid + for<'a> Decode<'a>,
+{
+    let Some(extension) = cert
+        .tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|ext| ext.extn_id == T::OID)
+    else {
+        return Ok(None);
+    };
+
+    T::from_der(extension.extn_value.as_bytes())
+        .map(Some)
+        .with_context(|| format!("failed to parse extension {}", oid_name(&T::OID)))
+}
+
+fn describe_public_key(cert: &Certificate) -> Result<String> {
+    let spki = &cert.tbs_certificate.subject_public_key_info;
+    match spki.algorithm.oid {
+        ID_EC_PUBLIC_KEY => Ok(match ec_curve(spki)? {
+            SECP_256_R_1 => "EC P-256 (256 bit)".to_string(),
+            SECP_384_R_1 => "EC P-384 (384 bit)".to_string(),
+            SECP_521_R_1 => "EC P-521 (521 bit)".to_string(),
+            other => format!("EC {}", oid_name(&other)),
+        }),
+        RSA_ENCRYPTION => {
+            let key = spki
+                .subject_public_key
+                .as_bytes()
+                .ok_or_else(|| anyhow!("public key is not byte aligned"))?;
+            let (modulus, _) = parse_rsa_public_key(key)?;
+            Ok(format!("RSA ({} bit)", bit_length(modulus)))
+        }
+        ID_ED_25519 => Ok("Ed25519 (256 bit)".to_string()),
+        other => Ok(oid_name(&other)),
+    }
+}
+
+fn extended_key_usage_name(oid: &ObjectIdentifier) -> String {
+    match *oid {
+        ID_KP_SERVER_AUTH => "Server Authentication".to_string(),
+        ID_KP_CLIENT_AUTH => "Client Authentication".to_string(),
+        ID_KP_CODE_SIGNING => "Code Signing".to_string(),
+        ID_KP_EMAIL_PROTECTION => "Email Protection".to_string(),
+        ID_KP_TIME_STAMPING => "Time Stamping".to_string(),
+        ID_KP_OCSP_SIGNING => "OCSP Signing".to_string(),
+        _ => oid_name(oid),
+    }
+}
+
+fn oid_name(oid: &ObjectIdentifier) -> String {
+    const_oid::db::DB
+        .by_oid(oid)
+        .map_or_else(|| oid.to_string(), str::to_string)
+}
+
+fn bit_length(unsigned_be: &[u8]) -> usize {
+    match unsigned_be.iter().position(|&b| b != 0) {
+        Some(idx) => (unsigned_be.len() - idx) * 8 - unsigned_be[idx].leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+fn format_ip(octets: &[u8]) -> String {
+    match octets.len() {
+        4 => IpAddr::from(<[u8; 4]>::try_from(octets).unwrap_or_default()).to_string(),
+        16 => IpAddr::from(<[u8; 16]>::try_from(octets).unwrap_or_default()).to_string(),
+        _ => colon_hex(octets),
+    }
+}
+
+fn colon_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use der::Decode;
+    use rcgen::{
+        BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyUsagePurpose,
+        SanType,
+    };
+    use x509_cert::Certificate;
+
+    use super::{bit_length, certificate_details, colon_hex};
+    use spire_agent::testing::self_signed;
+
+    #[test]
+    fn certificate_details_reports_extensions() {
+        let mut params = CertificateParams::new(vec!["svc.example.org".to_string()]).unwrap();
+        params
+            .subject_alt_names
+            .push(SanType::URI("spiffe://example.org/w".try_into().unwrap()));
+        params
+            .subject_alt_names
+            .push(SanType::IpAddress("10.0.0.1".parse().unwrap()));
+        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let cert = Certificate::from_der(&self_signed(params).der).unwrap();
+
+        let details = certificate_details(&cert).unwrap();
+        assert_eq!(details.uri_sans, ["spiffe://example.org/w"]);
+        assert_eq!(details.dns_sans, ["svc.example.org"]);
+        assert_eq!(details.ip_sans, ["10.0.0.1"]);
+        assert_eq!(details.public_key, "EC P-256 (256 bit)");
+        assert_eq!(details.signature_algorithm, "ecdsa-with-SHA256");
+        assert_eq!(details.key_usage, ["Certificate Sign", "CRL Sign"]);
+        assert_eq!(details.extended_key_usage, ["Client Authentication"]);
+        assert_eq!(details.basic_constraints.as_deref(), Some("CA:TRUE, pathlen:0"));
+        // 32 bytes as colon separated hex.
+        assert_eq!(details.sha256_fingerprint.len(), 32 * 3 - 1);
+    }
+
+    #[test]
+    fn bit_length_ignores_leading_zeros() {
+        assert_eq!(bit_length(&[0x00, 0x80, 0x00]), 16);
+        assert_eq!(bit_length(&[0x01, 0xff]), 9);
+        assert_eq!(bit_length(&[0x00]), 0);
+    }
+
+    #[test]
+    fn colon_hex_formats_bytes() {
+        assert_eq!(colon_hex(&[0x0a, 0xbc, 0x01]), "0A:BC:01");
+        assert_eq!(colon_hex(&[]), "");
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL};
+use const_oid::ObjectIdentifier;
+use const_oid::db::rfc5912::{
+    ID_EC_PUBLIC_KEY, RSA_ENCRYPTION, SECP_256_R_1, SECP_384_R_1, SECP_521_R_1,
+};
+use const_oid::db::rfc8410::ID_ED_25519;
+use der::asn1::UintRef;
+use der::{Decode, Encode, Reader};
+use serde_json::{Map, Value, json};
+use x509_cert::Certificate;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+
+const USE_X509_SVID: &str = "x509-svid";
+const USE_JWT_SVID: &str = "jwt-svid";
+
+/// Parses a JWKS document as returned by FetchJWTBundles, checking that it
+/// holds a `keys` array whose entries each name a key type.
+pub fn parse_jwks(bytes: &[u8]) -> Result<Map<String, Value>> {
+    let Value::Object(jwks) = serde_json::from_slice(bytes).context("JWKS is not valid JSON")?
+    else {
+        anyhow::bail!("JWKS is not a JSON object");
+    };
+
+    let keys = jwks
+        .get("keys")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("JWKS has no \"keys\" array"))?;
+    for (idx, key) in keys.iter().enumerate() {
+        if key.get("kty").and_then(Value::as_str).is_none() {
+            anyhow::bail!("JWKS key #{idx} has no \"kty\" member");
+        }
+    }
+
+    Ok(jwks)
+}
+
+/// Builds a SPIFFE bundle document (a JWKS whose keys carry `use` values of
+/// `x509-svid` and `jwt-svid`) from X.509 authorities and a JWT bundle.
+pub fn spiffe_bundle(
+    x509_authorities: &[Certificate],
+    jwt_bundle: Option<&Map<String, Value>>,
+) -> Result<Value> {
+    let mut keys = Vec::new();
+    for cert in x509_authorities {
+        keys.push(Value::Object(x509_authority_jwk(cert)?));
+    }
+
+    let mut bundle = Map::new();
+    if let Some(jwks) = jwt_bundle {
+        for key in jwks.get("keys").and_then(Value::as_array).into_iter().flatten() {
+            let mut key = key.clone();
+            if let Value::Object(members) = &mut key {
+                members.insert("use".to_string(), json!(USE_JWT_SVID));
+            }
+            keys.push(key);
+        }
+        // Carry over the SPIFFE bundle parameters when the agent sets them.
+        for param in ["spiffe_sequence", "spiffe_refresh_hint"] {
+            if let Some(value) = jwks.get(param) {
+                bundle.insert(param.to_string(), value.clone());
+            }
+        }
+    }
+
+    bundle.insert("keys".to_string(), Value::Array(keys));
+    Ok(Value::Object(bundle))
+}
+
+/// Converts an X.509 authority into a JWK with `use` set to `x509-svid` and
+/// the certificate carried in `x5c`, as the SPIFFE bundle format requires.
+pub fn x509_authority_jwk(cert: &Certificate) -> Result<Map<String, Value>> {
+    let mut jwk = public_key_jwk(cert)?;
+    let der = cert.to_der().context("failed to encode certificate")?;
+    jwk.insert("use".to_string(), json!(USE_X509_SVID));
+    jwk.insert("x5c".to_string(), json!([BASE64.encode(der)]));
+    Ok(jwk)
+}
+
+fn public_key_jwk(cert: &Certificate) -> Result<Map<String, Value>> {
+    let spki = &cert.tbs_certificate.subject_public_key_info;
+    let key = spki
+        .subject_public_key
+        .as_bytes()
+        .ok_or_else(|| anyhow!("public key is not byte aligned"))?;
+
+    let mut jwk = Map::new();
+    match spki.algorithm.oid {
+        ID_EC_PUBLIC_KEY => {
+            let crv = match ec_curve(spki)? {
+                SECP_256_R_1 => "P-256",
+                SECP_384_R_1 => "P-384",
+                SECP_521_R_1 => "P-521",
+                other => anyhow::bail!("unsupported EC curve {other}"),
+            };
+            // Uncompressed SEC1 point: 0x04 || X || Y.
+            let coords = match key.split_first() {
+                Some((0x04, coords)) if coords.len() % 2 == 0 => coords,
+                _ => anyhow::bail!("EC public key is not an uncompressed point"),
+            };
+            let (x, y) = coords.split_at(coords.len() / 2);
+            jwk.insert("kty".to_string(), json!("EC"));
+            jwk.insert("crv".to_string(), json!(crv));
+            jwk.insert("x".to_string(), json!(BASE64_URL.encode(x)));
+            jwk.insert("y".to_string(), json!(BASE64_URL.encode(y)));
+        }
+        RSA_ENCRYPTION => {
+            let (n, e) = parse_rsa_public_key(key)?;
+            jwk.insert("kty".to_string(), json!("RSA"));
+            jwk.insert("n".to_string(), json!(BASE64_URL.encode(n)));
+            jwk.insert("e".to_string(), json!(BASE64_URL.encode(e)));
+        }
+        ID_ED_25519 => {
+            jwk.insert("kty".to_string(), json!("OKP"));
+            jwk.insert("crv".to_string(), json!("Ed25519"));
+            jwk.insert("x".to_string(), json!(BASE64_URL.encode(key)));
+        }
+        other => anyhow::bail!("unsupported public key algorithm {other}"),
+    }
+
+    Ok(jwk)
+}
+
+/// Returns the named curve of an `id-ecPublicKey` subject public key.
+pub(crate) fn ec_curve(spki: &SubjectPublicKeyInfoOwned) -> Result<ObjectIdentifier> {
+    spki.algorithm
+        .parameters
+        .as_ref()
+        .ok_or_else(|| anyhow!("EC public key has no curve parameter"))?
+        .decode_as()
+        .context("failed to decode EC curve parameter")
+}
+
+// RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+pub(crate) fn parse_rsa_public_key(der_bytes: &[u8]) -> Result<(&[u8], &[u8])> {
+    let mut reader = der::SliceReader::new(der_bytes).context("failed to create DER reader")?;
+    let (n, e) = reader
+        .sequence(|seq| Ok((UintRef::decode(seq)?, UintRef::decode(seq)?)))
+        .context("failed to parse RSA public key")?;
+    Ok((n.as_bytes(), e.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use der::Decode;
+    use serde_json::json;
+    use x509_cert::Certificate;
+
+    use super::{parse_jwks, spiffe_bundle, x509_authority_jwk};
+    use spire_agent::testing::self_signed_with;
+
+    fn self_signed(alg: &'static rcgen::SignatureAlgorithm) -> Certificate {
+        let cert = self_signed_with(rcgen::CertificateParams::default(), alg);
+        Certificate::from_der(&cert.der).unwrap()
+    }
+
+    #[test]
+    fn x509_authority_jwk_encodes_ec_keys() {
+        let jwk = x509_authority_jwk(&self_signed(&rcgen::PKCS_ECDSA_P256_SHA256)).unwrap();
+        assert_eq!(jwk["kty"], "EC");
+        assert_eq!(jwk["crv"], "P-256");
+        assert_eq!(jwk["use"], "x509-svid");
+        // 32-byte coordinates encode to 43 base64url characters.
+        assert_eq!(jwk["x"].as_str().unwrap().len(), 43);
+        assert_eq!(jwk["y"].as_str().unwrap().len(), 43);
+        assert_eq!(jwk["x5c"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn x509_authority_jwk_encodes_ed25519_keys() {
+        let jwk = x509_authority_jwk(&self_signed(&rcgen::PKCS_ED25519)).unwrap();
+        assert_eq!(jwk["kty"], "OKP");
+        assert_eq!(jwk["crv"], "Ed25519");
+    }
+
+    #[test]
+    fn parse_jwks_requires_keys_with_types() {
+        assert!(parse_jwks(br#"{"keys":[{"kty":"EC","kid":"a"}]}"#).is_ok());
+        assert!(parse_jwks(br#"{"keys":[{"kid":"a"}]}"#).is_err());
+        assert!(parse_jwks(br#"{"nokeys":[]}"#).is_err());
+        assert!(parse_jwks(b"[]").is_err());
+        assert!(parse_jwks(b"not json").is_err());
+    }
+
+    #[test]
+    fn spiffe_bundle_merges_x509_and_jwt_keys() {
+        let jwks = parse_jwks(
+            br#"{"keys":[{"kty":"EC","kid":"k1","crv":"P-256","x":"a","y":"b"}],"spiffe_refresh_hint":300}"#,
+        )
+        .unwrap();
+        let authority = self_signed(&rcgen::PKCS_ECDSA_P256_SHA256);
+
+        let bundle = spiffe_bundle(&[authority], Some(&jwks)).unwrap();
+        let keys = bundle["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0]["use"], "x509-svid");
+        assert_eq!(keys[1]["use"], "jwt-svid");
+        assert_eq!(keys[1]["kid"], "k1");
+        assert_eq!(bundle["spiffe_refresh_hint"], json!(300));
+    }
+}
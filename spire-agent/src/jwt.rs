@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use chrono::{DateTime, Utc};
+use prost_types::value::Kind;
+use serde_json::{Map, Number, Value};
+
+use crate::grpc::{JwtBundlesResponse, Jwtsvid, ValidateJwtsvidResponse};
+use crate::spiffe_id::{SpiffeId, TrustDomain};
+use crate::x509::parse_trust_domain;
+
+// Used when a JWT-SVID carries no `exp` claim.
+const DEFAULT_REFRESH: Duration = Duration::from_secs(5 * 60);
+const MIN_REFRESH: Duration = Duration::from_secs(1);
+
+/// A JWT-SVID together with its claims.
+///
+/// The claims are decoded without checking the signature; use
+/// [`WorkloadApiClient::validate_jwt_svid`](crate::WorkloadApiClient::validate_jwt_svid)
+/// to validate a token received from a peer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JwtSvid {
+    spiffe_id: SpiffeId,
+    token: String,
+    hint: String,
+    claims: Map<String, Value>,
+}
+
+impl JwtSvid {
+    /// Decodes the claims of a JWS compact serialization issued to
+    /// `spiffe_id`.
+    pub fn parse(spiffe_id: SpiffeId, token: impl Into<String>) -> Result<Self> {
+        let token = token.into();
+        let claims = match decode_jwt_claims(&token)? {
+            Value::Object(claims) => claims,
+            _ => anyhow::bail!("malformed JWT claims: expected an object"),
+        };
+
+        Ok(Self {
+            spiffe_id,
+            token,
+            hint: String::new(),
+            claims,
+        })
+    }
+
+    /// Sets the operator-provided hint that tells SVIDs for the same
+    /// workload apart.
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = hint.into();
+        self
+    }
+
+    pub fn spiffe_id(&self) -> &SpiffeId {
+        &self.spiffe_id
+    }
+
+    /// The encoded token, as sent in an `Authorization: Bearer` header.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// The operator-provided hint; empty when the agent sent none.
+    pub fn hint(&self) -> &str {
+        &self.hint
+    }
+
+    pub fn claims(&self) -> &Map<String, Value> {
+        &self.claims
+    }
+
+    /// The `aud` claim, which JWT allows to be a single string or an array.
+    pub fn audience(&self) -> Vec<&str> {
+        match self.claims.get("aud") {
+            Some(Value::String(audience)) => vec![audience.as_str()],
+            Some(Value::Array(audience)) => audience.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The `exp` claim, if the token has one.
+    pub fn expiry(&self) -> Option<DateTime<Utc>> {
+        self.claims
+            .get("exp")
+            .and_then(Value::as_i64)
+            .and_then(|exp| DateTime::from_timestamp(exp, 0))
+    }
+
+    /// Time left until the `exp` claim, zero once it has passed.
+    pub fn remaining_lifetime(&self) -> Option<Duration> {
+        let remaining = self.expiry()? - Utc::now();
+        Some(remaining.to_std().unwrap_or_default())
+    }
+}
+
+/// How long JWT-SVIDs with `lifetime` left are used before fresh ones are
+/// fetched: half of it, but at least a second, and five minutes for tokens
+/// without an expiry.
+pub fn refresh_delay(lifetime: Option<Duration>) -> Duration {
+    lifetime.map_or(DEFAULT_REFRESH, |lifetime| (lifetime / 2).max(MIN_REFRESH))
+}
+
+impl TryFrom<&Jwtsvid> for JwtSvid {
+    type Error = anyhow::Error;
+
+    fn try_from(svid: &Jwtsvid) -> Result<Self> {
+        let spiffe_id = SpiffeId::new(&svid.spiffe_id)
+            .with_context(|| format!("invalid SPIFFE ID {:?}", svid.spiffe_id))?;
+        Ok(Self::parse(spiffe_id, &svid.svid)
+            .with_context(|| format!("invalid JWT-SVID for {}", svid.spiffe_id))?
+            .with_hint(&svid.hint))
+    }
+}
+
+/// The JWT authorities of one trust domain, as a JWKS document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JwtBundle {
+    trust_domain: TrustDomain,
+    jwks: Vec<u8>,
+}
+
+impl JwtBundle {
+    /// Wraps a JWKS document.
+    pub fn new(trust_domain: TrustDomain, jwks: Vec<u8>) -> Self {
+        Self { trust_domain, jwks }
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    /// The JWKS document exactly as the agent sent it.
+    pub fn jwks(&self) -> &[u8] {
+        &self.jwks
+    }
+}
+
+/// JWT bundles keyed by trust domain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JwtBundleSet {
+    bundles: BTreeMap<TrustDomain, JwtBundle>,
+}
+
+impl JwtBundleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a bundle, replacing any previous one for the same trust domain.
+    pub fn insert(&mut self, bundle: JwtBundle) {
+        self.bundles.insert(bundle.trust_domain.clone(), bundle);
+    }
+
+    pub fn get(&self, trust_domain: &TrustDomain) -> Option<&JwtBundle> {
+        self.bundles.get(trust_domain)
+    }
+
+    /// Bundles in trust domain order.
+    pub fn iter(&self) -> impl Iterator<Item = &JwtBundle> {
+        self.bundles.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bundles.is_empty()
+    }
+}
+
+impl TryFrom<&JwtBundlesResponse> for JwtBundleSet {
+    type Error = anyhow::Error;
+
+    fn try_from(resp: &JwtBundlesResponse) -> Result<Self> {
+        let mut set = Self::new();
+        for (trust_domain, jwks) in &resp.bundles {
+            set.insert(JwtBundle::new(
+                parse_trust_domain(trust_domain)?,
+                jwks.clone(),
+            ));
+        }
+        Ok(set)
+    }
+}
+
+/// The outcome of a successful `ValidateJWTSVID` call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidatedJwtSvid {
+    spiffe_id: SpiffeId,
+    claims: Map<String, Value>,
+}
+
+impl ValidatedJwtSvid {
+    /// The SPIFFE ID the token was issued to.
+    pub fn spiffe_id(&self) -> &SpiffeId {
+        &self.spiffe_id
+    }
+
+    /// The token's claims, as the agent reported them.
+    pub fn claims(&self) -> &Map<String, Value> {
+        &self.claims
+    }
+}
+
+impl TryFrom<&ValidateJwtsvidResponse> for ValidatedJwtSvid {
+    type Error = anyhow::Error;
+
+    fn try_from(resp: &ValidateJwtsvidResponse) -> Result<Self> {
+        let spiffe_id = SpiffeId::new(&resp.spiffe_id)
+            .with_context(|| format!("invalid SPIFFE ID {:?}", resp.spiffe_id))?;
+        Ok(Self {
+            spiffe_id,
+            claims: resp.claims.as_ref().map(struct_to_json).unwrap_or_default(),
+        })
+    }
+}
+
+// Decodes the payload of a JWS compact serialization without verifying the
+// signature; validation is the agent's job.
+fn decode_jwt_claims(token: &str) -> Result<Value> {
+    let mut parts = token.split('.');
+    let (Some(_header), Some(payload), Some(_signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        anyhow::bail!("malformed JWT: expected three segments");
+    };
+
+    let payload = BASE64_URL
+        .decode(payload.trim_end_matches('='))
+        .map_err(|err| anyhow!("malformed JWT payload: {err}"))?;
+    serde_json::from_slice(&payload).context("malformed JWT claims")
+}
+
+// Mirrors protojson's mapping of the `google.protobuf.Struct` well-known type.
+fn struct_to_json(value: &prost_types::Struct) -> Map<String, Value> {
+    value
+        .fields
+        .iter()
+        .map(|(key, value)| (key.clone(), value_to_json(value)))
+        .collect()
+}
+
+fn value_to_json(value: &prost_types::Value) -> Value {
+    match &value.kind {
+        None | Some(Kind::NullValue(_)) => Value::Null,
+        Some(Kind::NumberValue(n)) => number_to_json(*n),
+        Some(Kind::StringValue(s)) => Value::String(s.clone()),
+        Some(Kind::BoolValue(b)) => Value::Bool(*b),
+        Some(Kind::StructValue(s)) => Value::Object(struct_to_json(s)),
+        Some(Kind::ListValue(list)) => {
+            Value::Array(list.values.iter().map(value_to_json).collect())
+        }
+    }
+}
+
+// Struct numbers are doubles; keep whole values such as `exp` and `iat`
+// printing as integers rather than `1700000000.0`.
+fn number_to_json(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        Value::Number((n as i64).into())
+    } else {
+        Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::DateTime;
+    use prost_types::value::Kind;
+    use prost_types::{ListValue, Struct, Value};
+    use serde_json::json;
+
+    use super::{JwtSvid, decode_jwt_claims, refresh_delay, struct_to_json};
+    use crate::spiffe_id::SpiffeId;
+
+    // {"alg":"ES256"}.{"aud":["a"],"exp":1700000000,"sub":"spiffe://example.org/w"}.sig
+    const TOKEN: &str = "eyJhbGciOiJFUzI1NiJ9.\
+        eyJhdWQiOlsiYSJdLCJleHAiOjE3MDAwMDAwMDAsInN1YiI6InNwaWZmZTovL2V4YW1wbGUub3JnL3cifQ.\
+        c2ln";
+
+    fn value(kind: Kind) -> Value {
+        Value { kind: Some(kind) }
+    }
+
+    #[test]
+    fn decode_jwt_claims_reads_payload() {
+        let claims = decode_jwt_claims(TOKEN).expect("valid token");
+        assert_eq!(claims["sub"], "spiffe://example.org/w");
+        assert_eq!(claims["exp"], 1_700_000_000);
+        assert_eq!(claims["aud"][0], "a");
+    }
+
+    #[test]
+    fn decode_jwt_claims_rejects_malformed_tokens() {
+        assert!(decode_jwt_claims("abc").is_err());
+        assert!(decode_jwt_claims("a.b.c.d").is_err());
+        assert!(decode_jwt_claims("e30.!!!.c2ln").is_err());
+    }
+
+    #[test]
+    fn jwt_svid_exposes_audience_and_expiry() {
+        let id: SpiffeId = "spiffe://example.org/w".parse().unwrap();
+        let svid = JwtSvid::parse(id.clone(), TOKEN).unwrap();
+        assert_eq!(svid.audience(), ["a"]);
+        assert_eq!(svid.expiry(), DateTime::from_timestamp(1_700_000_000, 0));
+        assert!(JwtSvid::parse(id, "e30.WzFd.c2ln").is_err());
+    }
+
+    #[test]
+    fn refresh_delay_is_half_the_lifetime() {
+        let id: SpiffeId = "spiffe://example.org/w".parse().unwrap();
+        let expired = JwtSvid::parse(id, TOKEN).unwrap();
+        assert_eq!(expired.remaining_lifetime(), Some(Duration::ZERO));
+
+        assert_eq!(
+            refresh_delay(Some(Duration::from_secs(600))),
+            Duration::from_secs(300)
+        );
+        assert_eq!(refresh_delay(Some(Duration::ZERO)), Duration::from_secs(1));
+        assert_eq!(refresh_delay(None), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn struct_to_json_converts_claims() {
+        let claims = Struct {
+            fields: [
+                (
+                    "sub".to_string(),
+                    value(Kind::StringValue("spiffe://example.org/w".into())),
+                ),
+                ("exp".to_string(), value(Kind::NumberValue(1_700_000_000.0))),
+                ("ratio".to_string(), value(Kind::NumberValue(0.5))),
+                (
+                    "aud".to_string(),
+                    value(Kind::ListValue(ListValue {
+                        values: vec![value(Kind::StringValue("a".into()))],
+                    })),
+                ),
+                ("admin".to_string(), value(Kind::BoolValue(false))),
+                ("extra".to_string(), value(Kind::NullValue(0))),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        assert_eq!(
+            serde_json::Value::Object(struct_to_json(&claims)),
+            json!({
+                "sub": "spiffe://example.org/w",
+                "exp": 1_700_000_000,
+                "ratio": 0.5,
+                "aud": ["a"],
+                "admin": false,
+                "extra": null,
+            })
+        );
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::backoff::{Backoff, INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY, RECONNECT_JITTER};
+use crate::client::WorkloadApiClient;
+use crate::jwt::{JwtBundleSet, JwtSvid, refresh_delay};
+use crate::spiffe_id::SpiffeId;
+
+const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Configures a [`JwtSource`] before it starts.
+#[derive(Debug, Clone)]
+pub struct JwtSourceBuilder {
+    client: WorkloadApiClient,
+    backoff: Backoff,
+    fetch_timeout: Duration,
+}
+
+impl JwtSourceBuilder {
+    /// Delay between attempts after the bundle stream or a refresh fails.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Upper bound for one `FetchJWTSVID` call (default 30s). Callers asking
+    /// for the same SVID wait on that call, so a hung agent must not hold
+    /// them forever.
+    pub fn fetch_timeout(mut self, timeout: Duration) -> Self {
+        self.fetch_timeout = timeout;
+        self
+    }
+
+    /// Starts the background task that keeps the bundle stream open. Must be
+    /// called from within a Tokio runtime.
+    pub fn spawn(self) -> JwtSource {
+        let (bundles_tx, bundles) = watch::channel(None);
+        let (shutdown_tx, shutdown) = watch::channel(());
+        let shared = Arc::new(Shared {
+            client: self.client,
+            backoff: self.backoff,
+            fetch_timeout: self.fetch_timeout,
+            svids: Mutex::new(HashMap::new()),
+            last_error: Mutex::new(None),
+            shutdown,
+        });
+        let task = tokio::spawn(stream_bundles(Arc::clone(&shared), bundles_tx));
+
+        JwtSource {
+            shared,
+            bundles,
+            shutdown: Some(shutdown_tx),
+            task: Some(task),
+        }
+    }
+}
+
+/// JWT-SVIDs and JWT bundles, cached so that callers do not hit the agent
+/// on every request.
+///
+/// JWT-SVIDs are cached per audience set and SPIFFE ID and refreshed in the
+/// background once half of their lifetime has passed, for as long as they
+/// keep being asked for. Concurrent requests for the same SVID share one
+/// `FetchJWTSVID` call. The JWT bundles are kept current from a
+/// `FetchJWTBundles` stream, which is reopened with backoff when it fails.
+/// Dropping the source stops all background work; [`JwtSource::close`] also
+/// waits for the bundle stream to finish.
+#[derive(Debug)]
+pub struct JwtSource {
+    shared: Arc<Shared>,
+    bundles: watch::Receiver<Option<Arc<JwtBundleSet>>>,
+    shutdown: Option<watch::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl JwtSource {
+    /// A builder for a source using `client`, or a generated
+    /// `SpiffeWorkloadApiClient` from [`crate::rpc`].
+    pub fn builder(client: impl Into<WorkloadApiClient>) -> JwtSourceBuilder {
+        JwtSourceBuilder {
+            client: client.into(),
+            backoff: Backoff::new(INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY)
+                .with_jitter(RECONNECT_JITTER),
+            fetch_timeout: DEFAULT_FETCH_TIMEOUT,
+        }
+    }
+
+    /// Connects to a Workload API address, starts a source with the default
+    /// backoff, and waits up to `timeout` for the first bundle set.
+    pub async fn connect(address: &str, timeout: Duration) -> Result<Self> {
+        let client = WorkloadApiClient::connect(address).await?;
+        let source = Self::builder(client).spawn();
+        source.wait_ready(timeout).await?;
+        Ok(source)
+    }
+
+    /// Waits until the first bundle set has arrived. On timeout the error
+    /// includes the last stream failure, if any.
+    pub async fn wait_ready(&self, timeout: Duration) -> Result<Arc<JwtBundleSet>> {
+        let mut bundles = self.bundles.clone();
+        let ready = tokio::time::timeout(timeout, bundles.wait_for(Option::is_some)).await;
+
+        match ready {
+            Ok(Ok(bundles)) => Ok(Arc::clone(bundles.as_ref().expect("bundles are set"))),
+            Ok(Err(_)) => Err(anyhow!("JWT source is closed")),
+            Err(_) => {
+                let err = match self.last_error() {
+                    Some(last) => anyhow!(last),
+                    None => anyhow!("no response from the agent"),
+                };
+                Err(err.context(format!("no JWT bundles received within {timeout:?}")))
+            }
+        }
+    }
+
+    /// A JWT-SVID for `audience`: the default identity's unless `spiffe_id`
+    /// picks another. Served from the cache while the cached SVID is in the
+    /// first half of its lifetime.
+    ///
+    /// If the agent cannot be reached, a cached SVID that has not expired
+    /// yet is returned instead of the error.
+    pub async fn fetch_jwt_svid(
+        &self,
+        audience: &[String],
+        spiffe_id: Option<&SpiffeId>,
+    ) -> Result<JwtSvid> {
+        let key = SvidKey {
+            audience: audience.iter().cloned().collect(),
+            spiffe_id: spiffe_id.cloned(),
+        };
+        let entry = self.shared.entry(&key);
+        entry.used.store(true, Ordering::Relaxed);
+
+        // Holding the entry across the call makes concurrent callers wait for
+        // its result instead of asking the agent again.
+        let mut cached = entry.svid.lock().await;
+        if let Some(svid) = cached.as_ref()
+            && Instant::now() < svid.refresh_at
+        {
+            return Ok(svid.svid.clone());
+        }
+
+        let fetched = match self.shared.fetch(&key).await {
+            Ok(svid) => svid,
+            Err(err) => {
+                return match cached.as_ref() {
+                    Some(svid) if !svid.is_expired() => Ok(svid.svid.clone()),
+                    Some(_) => Err(err),
+                    None => {
+                        // Nothing was ever cached, so no refresh task owns
+                        // the entry; drop it rather than keep it forever.
+                        self.shared.remove_entry(&key, &entry);
+                        Err(err)
+                    }
+                };
+            }
+        };
+        let svid = fetched.svid.clone();
+        let first = cached.replace(fetched).is_none();
+        drop(cached);
+
+        if first {
+            tokio::spawn(refresh_svid(Arc::clone(&self.shared), key, entry));
+        }
+        Ok(svid)
+    }
+
+    /// The latest JWT bundles.
+    pub fn bundles(&self) -> Result<Arc<JwtBundleSet>> {
+        self.bundles
+            .borrow()
+            .clone()
+            .context("JWT source has not received JWT bundles yet")
+    }
+
+    /// The last stream or refresh failure since the last good update.
+    pub fn last_error(&self) -> Option<String> {
+        self.shared.last_error.lock().unwrap().clone()
+    }
+
+    /// Stops all background work and waits for the bundle stream to finish.
+    pub async fn close(mut self) {
+        self.shutdown.take();
+        if let Some(task) = self.task.take() {
+            // The task only ends by returning; a panic has already been
+            // reported by the runtime.
+            let _ = task.await;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SvidKey {
+    // A set, so that the same audiences in a different order share an entry.
+    audience: BTreeSet<String>,
+    spiffe_id: Option<SpiffeId>,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntry {
+    svid: tokio::sync::Mutex<Option<CachedSvid>>,
+    // Set by every lookup and cleared by every refresh, so that SVIDs nobody
+    // asks for any more are dropped instead of refreshed forever.
+    used: AtomicBool,
+}
+
+#[derive(Debug)]
+struct CachedSvid {
+    svid: JwtSvid,
+    refresh_at: Instant,
+    expires_at: Option<Instant>,
+}
+
+impl CachedSvid {
+    fn new(svid: JwtSvid) -> Self {
+        let now = Instant::now();
+        let lifetime = svid.remaining_lifetime();
+        Self {
+            svid,
+            refresh_at: now + refresh_delay(lifetime),
+            expires_at: lifetime.map(|lifetime| now + lifetime),
+        }
+    }
+
+    // `at`, or the expiry if that comes first: the SVID is never served from
+    // the cache past it.
+    fn cap(&self, at: Instant) -> Instant {
+        self.expires_at.map_or(at, |expires_at| at.min(expires_at))
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| Instant::now() >= expires_at)
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    client: WorkloadApiClient,
+    backoff: Backoff,
+    fetch_timeout: Duration,
+    svids: Mutex<HashMap<SvidKey, Arc<CacheEntry>>>,
+    last_error: Mutex<Option<String>>,
+    // Never written to; it resolves once the source drops the sender.
+    shutdown: watch::Receiver<()>,
+}
+
+impl Shared {
+    fn entry(&self, key: &SvidKey) -> Arc<CacheEntry> {
+        let mut svids = self.svids.lock().unwrap();
+        Arc::clone(svids.entry(key.clone()).or_default())
+    }
+
+    // Removes `entry` unless the key has been given a new entry since.
+    fn remove_entry(&self, key: &SvidKey, entry: &Arc<CacheEntry>) {
+        let mut svids = self.svids.lock().unwrap();
+        if svids
+            .get(key)
+            .is_some_and(|current| Arc::ptr_eq(current, entry))
+        {
+            svids.remove(key);
+        }
+    }
+
+    async fn fetch(&self, key: &SvidKey) -> Result<CachedSvid> {
+        let audience: Vec<String> = key.audience.iter().cloned().collect();
+        let mut client = self.client.clone();
+        let fetch = client.fetch_jwt_svid(&audience, key.spiffe_id.as_ref());
+        let svid = tokio::time::timeout(self.fetch_timeout, fetch)
+            .await
+            .context("timed out fetching JWT-SVID")??;
+        Ok(CachedSvid::new(svid))
+    }
+
+    fn record_error(&self, err: &anyhow::Error) {
+        self.last_error.lock().unwrap().replace(format!("{err:#}"));
+    }
+}
+
+// Re-fetches one cached SVID at half its lifetime until it goes unused for
+// a whole refresh period or the source shuts down.
+async fn refresh_svid(shared: Arc<Shared>, key: SvidKey, entry: Arc<CacheEntry>) {
+    let mut shutdown = shared.shutdown.clone();
+    let mut backoff = shared.backoff.clone();
+
+    loop {
+        let Some(refresh_at) = entry.svid.lock().await.as_ref().map(|svid| svid.refresh_at) else {
+            return;
+        };
+        tokio::select! {
+            _ = shutdown.changed() => return,
+            () = tokio::time::sleep_until(refresh_at) => {}
+        }
+
+        if !entry.used.swap(false, Ordering::Relaxed) {
+            shared.remove_entry(&key, &entry);
+            return;
+        }
+
+        // Callers keep getting the current SVID while the refresh is in
+        // flight, so it is fetched without holding the entry.
+        if let Some(cached) = entry.svid.lock().await.as_mut() {
+            cached.refresh_at = cached.cap(Instant::now() + shared.fetch_timeout);
+        }
+        let fetched = shared.fetch(&key).await;
+
+        let mut cached = entry.svid.lock().await;
+        match fetched {
+            Ok(svid) => {
+                backoff.reset();
+                *cached = Some(svid);
+            }
+            Err(err) => {
+                shared.record_error(&err);
+                // Keep serving the old SVID while it is valid and try again
+                // soon. Once it has expired nobody is served from the entry
+                // any more, and the next lookup fetches on its own.
+                match cached.as_mut() {
+                    Some(svid) if !svid.is_expired() => {
+                        svid.refresh_at = svid.cap(Instant::now() + backoff.next_delay());
+                        entry.used.store(true, Ordering::Relaxed);
+                    }
+                    _ => {
+                        drop(cached);
+                        shared.remove_entry(&key, &entry);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn stream_bundles(shared: Arc<Shared>, bundles: watch::Sender<Option<Arc<JwtBundleSet>>>) {
+    let mut shutdown = shared.shutdown.clone();
+    let mut backoff = shared.backoff.clone();
+
+    loop {
+        let result = tokio::select! {
+            _ = shutdown.changed() => return,
+            result = watch_bundles(&shared, &bundles, &mut backoff) => result,
+        };
+        let err = result
+            .err()
+            .unwrap_or_else(|| anyhow!("Workload API stream closed by the agent"));
+        shared.record_error(&err);
+
+        tokio::select! {
+            _ = shutdown.changed() => return,
+            () = tokio::time::sleep(backoff.next_delay()) => {}
+        }
+    }
+}
+
+// Publishes every bundle set until the stream ends; `Ok` means the agent
+// closed it.
+async fn watch_bundles(
+    shared: &Shared,
+    bundles: &watch::Sender<Option<Arc<JwtBundleSet>>>,
+    backoff: &mut Backoff,
+) -> Result<()> {
+    let mut stream = shared.client.clone().watch_jwt_bundles().await?;
+
+    while let Some(set) = stream.next().await? {
+        backoff.reset();
+        shared.last_error.lock().unwrap().take();
+        bundles.send_replace(Some(Arc::new(set)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc;
+    use tokio::time::Instant;
+    use tonic::Code;
+
+    use super::JwtSource;
+    use crate::WorkloadApiClient;
+    use crate::backoff::Backoff;
+    use crate::grpc::JwtBundlesResponse;
+    use crate::testing::{DEFAULT_SPIFFE_ID, start_agent};
+
+    #[tokio::test]
+    async fn jwt_source_caches_and_coalesces_svid_requests() {
+        let agent = start_agent(Duration::from_secs(600)).await;
+        let client = WorkloadApiClient::connect(&agent.address).await.unwrap();
+        let source = JwtSource::builder(client).spawn();
+        let audience = vec!["a".to_string(), "b".to_string()];
+
+        let (first, second) = tokio::join!(
+            source.fetch_jwt_svid(&audience, None),
+            source.fetch_jwt_svid(&audience, None)
+        );
+        assert_eq!(first.unwrap(), second.unwrap());
+        assert_eq!(agent.jwt_svid_calls(), 1);
+
+        let reversed = vec!["b".to_string(), "a".to_string()];
+        let svid = source.fetch_jwt_svid(&reversed, None).await.unwrap();
+        assert_eq!(svid.spiffe_id(), DEFAULT_SPIFFE_ID);
+        assert_eq!(agent.jwt_svid_calls(), 1);
+
+        let other_id = "spiffe://example.org/other".parse().unwrap();
+        let other = source
+            .fetch_jwt_svid(&audience, Some(&other_id))
+            .await
+            .unwrap();
+        assert_eq!(other.spiffe_id(), "spiffe://example.org/other");
+        assert_eq!(agent.jwt_svid_calls(), 2);
+
+        source.close().await;
+    }
+
+    #[tokio::test]
+    async fn jwt_source_refreshes_at_half_life() {
+        let agent = start_agent(Duration::from_secs(4)).await;
+        let client = WorkloadApiClient::connect(&agent.address).await.unwrap();
+        let source = JwtSource::builder(client).spawn();
+        let audience = vec!["a".to_string()];
+
+        let first = source.fetch_jwt_svid(&audience, None).await.unwrap();
+        let fetched_at = Instant::now();
+        // The token lives 3 to 4 seconds, so it is refreshed 1.5 to 2 seconds
+        // in without anyone asking for it, and not again for as long.
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while agent.jwt_svid_calls() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(fetched_at.elapsed() >= Duration::from_secs(1));
+
+        // Lookups keep getting the first token until the refresh lands.
+        tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                let svid = source.fetch_jwt_svid(&audience, None).await.unwrap();
+                if svid.token() != first.token() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(agent.jwt_svid_calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn jwt_source_serves_cached_svid_until_failed_refreshes_expire_it() {
+        let agent = start_agent(Duration::from_secs(3)).await;
+        let client = WorkloadApiClient::connect(&agent.address).await.unwrap();
+        let source = JwtSource::builder(client)
+            .backoff(Backoff::new(
+                Duration::from_millis(100),
+                Duration::from_millis(200),
+            ))
+            .spawn();
+        let audience = vec!["a".to_string()];
+
+        let first = source.fetch_jwt_svid(&audience, None).await.unwrap();
+        agent.fail_jwt_svids(Some(Code::Unavailable));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while agent.jwt_svid_calls() < 3 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let cached = source.fetch_jwt_svid(&audience, None).await.unwrap();
+        assert_eq!(first.token(), cached.token());
+        assert!(source.last_error().unwrap().contains("injected failure"));
+
+        // Nobody asks again, so the entry and its refresh task go away once
+        // the SVID has expired.
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !source.shared.svids.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let calls = agent.jwt_svid_calls();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(agent.jwt_svid_calls(), calls);
+    }
+
+    #[tokio::test]
+    async fn jwt_source_times_out_and_forgets_failed_first_fetch() {
+        let agent = start_agent(Duration::from_secs(600)).await;
+        let client = WorkloadApiClient::connect(&agent.address).await.unwrap();
+        // The fake agent takes 50ms to answer.
+        let source = JwtSource::builder(client)
+            .fetch_timeout(Duration::from_millis(1))
+            .spawn();
+
+        let err = source
+            .fetch_jwt_svid(&["a".to_string()], None)
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("timed out"), "{err:#}");
+        assert!(source.shared.svids.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn jwt_source_follows_bundle_stream() {
+        let agent = start_agent(Duration::from_secs(600)).await;
+        let client = WorkloadApiClient::connect(&agent.address).await.unwrap();
+        let source = JwtSource::builder(client).spawn();
+        assert!(source.bundles().is_err());
+
+        let (updates, stream) = mpsc::channel(4);
+        agent.jwt_bundle_streams.send(stream).await.unwrap();
+        let bundles = |jwks: &[u8]| {
+            Ok(JwtBundlesResponse {
+                bundles: [("spiffe://example.org".to_string(), jwks.to_vec())]
+                    .into_iter()
+                    .collect(),
+            })
+        };
+        updates.send(bundles(b"{\"keys\":[]}")).await.unwrap();
+
+        let trust_domain = "example.org".parse().unwrap();
+        let set = source.wait_ready(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(set.get(&trust_domain).unwrap().jwks(), b"{\"keys\":[]}");
+
+        updates.send(bundles(b"{\"keys\":[{}]}")).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while source.bundles().unwrap().get(&trust_domain).unwrap().jwks() != b"{\"keys\":[{}]}"
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        source.close().await;
+    }
+}
@@ -0,0 +1,56 @@
+//! A client for the [SPIFFE Workload API], the library behind the
+//! `spire-agent` CLI.
+//!
+//! [`WorkloadApiClient`] fetches and watches X.509-SVIDs, JWT-SVIDs and trust
+//! bundles and returns them as the typed values in [`x509`] and [`jwt`]:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use spire_agent::WorkloadApiClient;
+//!
+//! let mut client = WorkloadApiClient::connect("unix:///tmp/spire-agent/public/api.sock").await?;
+//! let context = client.fetch_x509_context().await?;
+//! for svid in context.svids() {
+//!     println!("{} ({} certificates)", svid.spiffe_id(), svid.cert_chain().len());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Long-running services should hold an [`X509Source`] instead, which keeps
+//! the stream open, reconnects on failure and always has the latest SVID,
+//! and a [`JwtSource`], which caches JWT-SVIDs and refreshes them before
+//! they expire. [`tls`] turns a live X.509 context into rustls configs for
+//! mutual TLS that authorize peers by SPIFFE ID.
+//!
+//! SPIFFE IDs and trust domains are carried as [`SpiffeId`] and
+//! [`TrustDomain`], which are checked against the SPIFFE ID specification
+//! when they are parsed, so values received from the agent are valid by
+//! construction.
+//!
+//! The generated protocol types and the raw gRPC client stay reachable
+//! through [`grpc`] and [`rpc`] for callers that need the wire format.
+//!
+//! [SPIFFE Workload API]: https://github.com/spiffe/spiffe/blob/main/standards/SPIFFE_Workload_API.md
+
+pub mod backoff;
+pub mod client;
+pub mod grpc;
+pub mod jwt;
+pub mod jwt_source;
+pub mod rpc;
+pub mod spiffe_id;
+pub mod tls;
+pub mod x509;
+pub mod x509_source;
+
+#[cfg(any(test, feature = "test-util"))]
+#[doc(hidden)]
+pub mod testing;
+
+pub use client::WorkloadApiClient;
+pub use jwt::{JwtBundle, JwtBundleSet, JwtSvid, ValidatedJwtSvid};
+pub use jwt_source::JwtSource;
+pub use spiffe_id::{SpiffeId, SpiffeIdError, TrustDomain};
+pub use x509::{X509Bundle, X509BundleSet, X509Context, X509Svid};
+pub use x509_source::{SvidSelector, X509Source};
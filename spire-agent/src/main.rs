@@ -1,8 +1,23 @@
+mod atomic_write;
 mod commands;
+mod compat;
+mod daemon;
+mod duration;
+mod expiry;
+mod fetch_bundle;
+mod fetch_jwt;
+mod fetch_jwt_bundle;
 mod fetch_x509;
 mod healthcheck;
-mod rpc;
-mod grpc;
+mod inspect;
+mod jwk;
+mod notify;
+mod output;
+mod pkcs12;
+mod retry;
+mod validate_jwt;
+mod verify;
+mod watch;
 
 #[tokio::main]
 async fn main() {
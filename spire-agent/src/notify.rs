@@ -0,0 +1,179 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+
+/// Where `api daemon` delivers its reload signal.
+#[derive(Debug, Clone)]
+pub enum SignalTarget {
+    /// The PID stored in a pidfile, re-read on every update.
+    PidFile(PathBuf),
+    /// Every process whose name (`/proc/<pid>/comm`) matches exactly.
+    ProcessName(String),
+}
+
+/// How `api daemon` tells the workload that its files changed.
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    /// Program and arguments run after every write.
+    pub command: Vec<String>,
+    /// Signal number and the processes it is sent to.
+    pub signal: Option<(i32, SignalTarget)>,
+}
+
+impl Notifier {
+    /// Runs the command and sends the signal. Both are attempted even if the
+    /// other fails; the first error is returned.
+    pub async fn notify(&self, silent: bool) -> Result<()> {
+        let command = self.run_command(silent).await;
+        let signal = self.send_signal(silent);
+        command.and(signal)
+    }
+
+    async fn run_command(&self, silent: bool) -> Result<()> {
+        let Some((program, args)) = self.command.split_first() else {
+            return Ok(());
+        };
+        let status = tokio::process::Command::new(program)
+            .args(args)
+            .status()
+            .await
+            .with_context(|| format!("failed to run {program}"))?;
+        if !status.success() {
+            anyhow::bail!("{program} exited with {status}");
+        }
+        if !silent {
+            println!("Ran {program}.");
+        }
+        Ok(())
+    }
+
+    fn send_signal(&self, silent: bool) -> Result<()> {
+        let Some((signal, target)) = &self.signal else {
+            return Ok(());
+        };
+        let pids = match target {
+            SignalTarget::PidFile(path) => vec![read_pid_file(path)?],
+            SignalTarget::ProcessName(name) => {
+                let pids = find_processes(name)?;
+                if pids.is_empty() {
+                    anyhow::bail!("no process named {name} is running");
+                }
+                pids
+            }
+        };
+        for pid in pids {
+            kill(pid, *signal)?;
+            if !silent {
+                println!("Sent {} to process {}.", signal_name(*signal), pid);
+            }
+        }
+        Ok(())
+    }
+}
+
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("TERM", libc::SIGTERM),
+    ("CONT", libc::SIGCONT),
+    ("WINCH", libc::SIGWINCH),
+];
+
+/// Parses `SIGHUP`, `HUP`, `hup` or a signal number.
+pub fn parse_signal(s: &str) -> Result<i32> {
+    if let Ok(number) = s.parse::<i32>() {
+        if number <= 0 {
+            anyhow::bail!("invalid signal number {number}");
+        }
+        return Ok(number);
+    }
+    let upper = s.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, signal)| *signal)
+        .ok_or_else(|| anyhow!("unknown signal {s:?}"))
+}
+
+fn signal_name(signal: i32) -> String {
+    SIGNALS
+        .iter()
+        .find(|(_, known)| *known == signal)
+        .map_or_else(
+            || format!("signal {signal}"),
+            |(name, _)| format!("SIG{name}"),
+        )
+}
+
+fn read_pid_file(path: &std::path::Path) -> Result<i32> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read pidfile {}", path.display()))?;
+    let pid: i32 = contents
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid PID in {}", path.display()))?;
+    if pid <= 0 {
+        anyhow::bail!("invalid PID {pid} in {}", path.display());
+    }
+    Ok(pid)
+}
+
+fn find_processes(name: &str) -> Result<Vec<i32>> {
+    let own_pid = std::process::id() as i32;
+    let mut pids = Vec::new();
+    for entry in fs::read_dir("/proc").context("failed to list /proc")? {
+        let Ok(entry) = entry else { continue };
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|pid| pid.parse::<i32>().ok())
+        else {
+            continue;
+        };
+        // Processes can exit while we scan; skip them.
+        let Ok(comm) = fs::read_to_string(entry.path().join("comm")) else {
+            continue;
+        };
+        if pid != own_pid && comm.trim_end_matches('\n') == name {
+            pids.push(pid);
+        }
+    }
+    pids.sort_unstable();
+    Ok(pids)
+}
+
+fn kill(pid: i32, signal: i32) -> Result<()> {
+    // SAFETY: kill(2) has no memory-safety preconditions.
+    if unsafe { libc::kill(pid, signal) } != 0 {
+        let err = std::io::Error::last_os_error();
+        return Err(err).with_context(|| format!("failed to signal process {pid}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_processes, parse_signal, signal_name};
+
+    #[test]
+    fn parse_signal_accepts_names_and_numbers() {
+        assert_eq!(parse_signal("SIGHUP").unwrap(), libc::SIGHUP);
+        assert_eq!(parse_signal("usr1").unwrap(), libc::SIGUSR1);
+        assert_eq!(parse_signal("15").unwrap(), libc::SIGTERM);
+        assert!(parse_signal("SIGNOPE").is_err());
+        assert!(parse_signal("0").is_err());
+        assert_eq!(signal_name(libc::SIGHUP), "SIGHUP");
+    }
+
+    #[test]
+    fn find_processes_skips_own_process() {
+        let comm = std::fs::read_to_string("/proc/self/comm").unwrap();
+        let pids = find_processes(comm.trim_end()).unwrap();
+        assert!(!pids.contains(&(std::process::id() as i32)));
+    }
+}
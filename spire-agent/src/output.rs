@@ -0,0 +1,25 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Pretty,
+    Json,
+}
+
+// Prints a value as a single line of JSON, matching the Go CLI's
+// `json.Encoder` output so results can be piped into `jq`.
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    let json = serde_json::to_string(value).context("failed to encode JSON output")?;
+    println!("{json}");
+    Ok(())
+}
+
+/// File format used by `--write` for SVIDs and bundles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum WriteFormat {
+    #[default]
+    Pem,
+    Pkcs12,
+}
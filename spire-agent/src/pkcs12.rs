@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use p12_keystore::{Certificate, KeyStore, KeyStoreEntry, PrivateKey, PrivateKeyChain};
+use sha2::{Digest, Sha256};
+use spire_agent::{X509Bundle, X509Svid};
+
+/// Environment variable consulted for the PKCS#12 passphrase when neither
+/// `--passphrase` nor `--passphrase-file` is given.
+pub const PASSPHRASE_ENV: &str = "SPIRE_AGENT_PKCS12_PASSPHRASE";
+
+/// Picks the passphrase from `--passphrase`, from the first line of
+/// `--passphrase-file`, or, when neither flag is given, from `env`: the value
+/// of [`PASSPHRASE_ENV`].
+pub fn resolve_passphrase(
+    passphrase: Option<&str>,
+    file: Option<&Path>,
+    env: Option<&str>,
+) -> Result<String> {
+    let passphrase = match (passphrase, file, env) {
+        (Some(passphrase), _, _) => passphrase.to_string(),
+        (None, Some(file), _) => {
+            let contents = fs::read_to_string(file)
+                .with_context(|| format!("failed to read passphrase file {}", file.display()))?;
+            contents.lines().next().unwrap_or_default().to_string()
+        }
+        (None, None, Some(env)) => env.to_string(),
+        (None, None, None) => anyhow::bail!(
+            "--format pkcs12 requires a passphrase (--passphrase, --passphrase-file or {PASSPHRASE_ENV})"
+        ),
+    };
+
+    if passphrase.is_empty() {
+        anyhow::bail!("PKCS#12 passphrase must not be empty");
+    }
+    Ok(passphrase)
+}
+
+/// Builds a keystore holding the SVID's private key with its certificate chain
+/// plus every bundle CA as a trusted certificate entry, so a single file can
+/// serve as both `javax.net.ssl.keyStore` and `javax.net.ssl.trustStore`.
+pub fn svid_keystore(svid: &X509Svid, bundle: &X509Bundle, passphrase: &str) -> Result<Vec<u8>> {
+    let key = PrivateKey::from_der(svid.private_key())
+        .map_err(|err| anyhow!("invalid x509_svid_key: {err}"))?;
+    let chain = p12_certificates(svid.cert_chain())?;
+    let Some(leaf) = chain.first() else {
+        anyhow::bail!("SVID {} has no certificates", svid.spiffe_id());
+    };
+
+    // Java pairs the key with its certificate through the local key ID; the
+    // leaf fingerprint is what keytool and OpenSSL use as well.
+    let local_key_id = Sha256::digest(leaf.as_der()).to_vec();
+    let mut keystore = KeyStore::new();
+    keystore.add_entry(
+        &svid.spiffe_id().to_string(),
+        KeyStoreEntry::PrivateKeyChain(PrivateKeyChain::new(local_key_id, key, chain)),
+    );
+    add_trusted_certificates(&mut keystore, bundle)?;
+
+    write_keystore(&keystore, passphrase)
+}
+
+/// Builds a truststore-only keystore from a bundle.
+pub fn truststore(bundle: &X509Bundle, passphrase: &str) -> Result<Vec<u8>> {
+    let mut keystore = KeyStore::new();
+    add_trusted_certificates(&mut keystore, bundle)?;
+    write_keystore(&keystore, passphrase)
+}
+
+fn add_trusted_certificates(keystore: &mut KeyStore, bundle: &X509Bundle) -> Result<()> {
+    for (ca_num, cert) in (1..).zip(p12_certificates(bundle.authorities())?) {
+        keystore.add_entry(&format!("ca-{ca_num}"), KeyStoreEntry::Certificate(cert));
+    }
+    Ok(())
+}
+
+fn p12_certificates(certs: &[Vec<u8>]) -> Result<Vec<Certificate>> {
+    certs
+        .iter()
+        .map(|der| {
+            Certificate::from_der(der).map_err(|err| anyhow!("invalid certificate: {err}"))
+        })
+        .collect()
+}
+
+fn write_keystore(keystore: &KeyStore, passphrase: &str) -> Result<Vec<u8>> {
+    keystore
+        .writer(passphrase)
+        .write()
+        .map_err(|err| anyhow!("failed to encode PKCS#12 keystore: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use p12_keystore::{KeyStore, KeyStoreEntry, Pkcs12ImportPolicy};
+    use spire_agent::{X509Bundle, X509Svid};
+
+    use super::{resolve_passphrase, svid_keystore, truststore};
+    use spire_agent::testing::TestCa;
+
+    fn svid() -> (X509Svid, X509Bundle) {
+        let ca = TestCa::new("root");
+        (ca.svid("spiffe://example.org/w"), ca.bundle("example.org"))
+    }
+
+    #[test]
+    fn svid_keystore_round_trips() {
+        let (svid, bundle) = svid();
+        let p12 = svid_keystore(&svid, &bundle, "changeit").unwrap();
+
+        let keystore = KeyStore::from_pkcs12(&p12, "changeit", Pkcs12ImportPolicy::Strict).unwrap();
+        let (alias, chain) = keystore.private_key_chain().expect("key entry");
+        assert_eq!(alias, "spiffe://example.org/w");
+        assert_eq!(chain.key().as_der(), svid.private_key());
+        assert_eq!(chain.certs()[0].as_der(), svid.leaf());
+        assert!(matches!(
+            keystore.entry("ca-1"),
+            Some(KeyStoreEntry::Certificate(cert)) if cert.as_der() == bundle.authorities()[0]
+        ));
+
+        assert!(KeyStore::from_pkcs12(&p12, "wrong", Pkcs12ImportPolicy::Strict).is_err());
+    }
+
+    #[test]
+    fn truststore_holds_only_certificates() {
+        let (_, bundle) = svid();
+        let p12 = truststore(&bundle, "changeit").unwrap();
+
+        let keystore = KeyStore::from_pkcs12(&p12, "changeit", Pkcs12ImportPolicy::Strict).unwrap();
+        assert!(keystore.private_key_chain().is_none());
+        assert_eq!(keystore.entries_len(), 1);
+    }
+
+    #[test]
+    fn resolve_passphrase_prefers_flag_then_file_then_env() {
+        let mut file = std::env::temp_dir();
+        file.push(format!("spire-agent-passphrase-{}", std::process::id()));
+        std::fs::File::create(&file)
+            .unwrap()
+            .write_all(b"from-file\n")
+            .unwrap();
+
+        assert_eq!(
+            resolve_passphrase(Some("flag"), Some(&file), Some("env")).unwrap(),
+            "flag"
+        );
+        assert_eq!(resolve_passphrase(None, Some(&file), None).unwrap(), "from-file");
+        // The environment variable only applies when neither flag is given.
+        assert_eq!(
+            resolve_passphrase(None, Some(&file), Some("env")).unwrap(),
+            "from-file"
+        );
+        assert_eq!(resolve_passphrase(None, None, Some("env")).unwrap(), "env");
+        assert!(resolve_passphrase(None, None, None).is_err());
+        assert!(resolve_passphrase(Some(""), None, None).is_err());
+        assert!(resolve_passphrase(None, None, Some("")).is_err());
+
+        std::fs::remove_file(file).unwrap();
+    }
+}
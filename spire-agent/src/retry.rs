@@ -0,0 +1,237 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+use spire_agent::backoff::{
+    Backoff, INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY, RECONNECT_JITTER,
+};
+use tokio::time::Instant;
+use tonic::{Code, Status};
+
+use crate::fetch_x509::format_duration_seconds;
+
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(250);
+const RETRY_JITTER: f64 = 0.2;
+
+/// SPIRE answers with this `PermissionDenied` message while the entries for a
+/// workload are still being synced to the agent.
+const NO_IDENTITY_ISSUED: &str = "no identity issued";
+
+/// How long, and how often, to retry transient Workload API failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Overall time budget for retries; zero makes a single attempt.
+    pub deadline: Duration,
+    /// Upper bound for the delay between two attempts.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            deadline: Duration::ZERO,
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Jittered exponential backoff bounded by `max_delay`.
+    pub fn backoff(&self, initial: Duration) -> Backoff {
+        Backoff::new(initial.min(self.max_delay), self.max_delay).with_jitter(RETRY_JITTER)
+    }
+}
+
+/// Runs `operation` until it succeeds, fails with an error that is not
+/// transient, or the policy's deadline would be exceeded by the next delay.
+pub async fn retry<T, F, Fut>(policy: &RetryPolicy, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let deadline = Instant::now() + policy.deadline;
+    let mut backoff = policy.backoff(INITIAL_RETRY_DELAY);
+
+    loop {
+        let err = match operation().await {
+            Ok(value) => return Ok(value),
+            Err(err) if is_transient(&err) => err,
+            Err(err) => return Err(err),
+        };
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(err);
+        }
+        let delay = backoff.next_delay().min(remaining);
+        eprintln!(
+            "Error: {err:#}; retrying in {}",
+            format_duration_seconds(delay)
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Reconnect decisions for the streaming commands, `watch` and `daemon`.
+///
+/// Until the agent delivers the first update the retry policy applies: an
+/// error that is not transient ends the command, and so does a transient one
+/// once the deadline has passed. A zero deadline keeps retrying transient
+/// errors, as these commands always have. After the first update every
+/// failure leads to a reconnect with a backoff of up to 30s.
+pub struct Reconnect {
+    deadline: Option<Instant>,
+    connected: bool,
+    startup_backoff: Backoff,
+    backoff: Backoff,
+}
+
+impl Reconnect {
+    pub fn new(policy: &RetryPolicy) -> Self {
+        Self {
+            deadline: (!policy.deadline.is_zero()).then(|| Instant::now() + policy.deadline),
+            connected: false,
+            startup_backoff: policy.backoff(INITIAL_RECONNECT_DELAY),
+            backoff: Backoff::new(INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY)
+                .with_jitter(RECONNECT_JITTER),
+        }
+    }
+
+    /// Records that the agent delivered an update.
+    pub fn connected(&mut self) {
+        self.connected = true;
+        self.backoff.reset();
+    }
+
+    /// Whether the command should fail with `err` instead of reconnecting.
+    pub fn gives_up_on(&self, err: &anyhow::Error) -> bool {
+        if self.connected {
+            return false;
+        }
+        !is_transient(err) || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// How long to wait before the next connection attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        if self.connected {
+            return self.backoff.next_delay();
+        }
+        let delay = self.startup_backoff.next_delay();
+        match self.deadline {
+            Some(deadline) => delay.min(deadline.saturating_duration_since(Instant::now())),
+            None => delay,
+        }
+    }
+}
+
+/// Whether `err` is worth retrying: the agent is unreachable or not ready
+/// yet, a call timed out, or the workload has not been issued an identity.
+fn is_transient(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(status) = cause.downcast_ref::<Status>() {
+            return is_transient_status(status);
+        }
+        cause.is::<tonic::transport::Error>() || cause.is::<tokio::time::error::Elapsed>()
+    })
+}
+
+fn is_transient_status(status: &Status) -> bool {
+    match status.code() {
+        Code::Unavailable => true,
+        Code::PermissionDenied => status.message().contains(NO_IDENTITY_ISSUED),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use anyhow::Context;
+    use tonic::Status;
+
+    use super::{Reconnect, RetryPolicy, is_transient, retry};
+
+    fn policy(deadline: Duration) -> RetryPolicy {
+        RetryPolicy {
+            deadline,
+            max_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn is_transient_classifies_statuses() {
+        let unavailable = Err::<(), _>(Status::unavailable("agent not ready"))
+            .context("failed to fetch x509 svid")
+            .unwrap_err();
+        assert!(is_transient(&unavailable));
+
+        let no_identity = anyhow::Error::new(Status::permission_denied("no identity issued"));
+        assert!(is_transient(&no_identity));
+
+        let denied = anyhow::Error::new(Status::permission_denied("not allowed"));
+        assert!(!is_transient(&denied));
+        assert!(!is_transient(&anyhow::anyhow!("malformed response")));
+    }
+
+    #[tokio::test]
+    async fn retry_retries_transient_errors_until_success() {
+        let attempts = AtomicUsize::new(0);
+        let result = retry(&policy(Duration::from_secs(5)), || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(Status::unavailable("agent not ready").into())
+            } else {
+                Ok("svid")
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "svid");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_stops_on_permanent_errors_and_without_deadline() {
+        let attempts = AtomicUsize::new(0);
+        let result: anyhow::Result<()> = retry(&policy(Duration::from_secs(5)), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Status::invalid_argument("bad request").into())
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        let attempts = AtomicUsize::new(0);
+        let result: anyhow::Result<()> = retry(&RetryPolicy::default(), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Status::unavailable("agent not ready").into())
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn reconnect_applies_policy_until_first_update() {
+        let unavailable = anyhow::Error::new(Status::unavailable("agent not ready"));
+        let denied = anyhow::Error::new(Status::permission_denied("not allowed"));
+
+        // Without a deadline only permanent errors end the command.
+        let mut reconnect = Reconnect::new(&policy(Duration::ZERO));
+        assert!(!reconnect.gives_up_on(&unavailable));
+        assert!(reconnect.gives_up_on(&denied));
+        assert!(reconnect.next_delay() <= Duration::from_millis(10));
+
+        let reconnect = Reconnect::new(&policy(Duration::from_millis(1)));
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(reconnect.gives_up_on(&unavailable));
+
+        // Once streaming, every failure is followed by a reconnect.
+        let mut reconnect = Reconnect::new(&policy(Duration::from_millis(1)));
+        reconnect.connected();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(!reconnect.gives_up_on(&unavailable));
+        assert!(!reconnect.gives_up_on(&denied));
+    }
+}
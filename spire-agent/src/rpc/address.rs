@@ -0,0 +1,154 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow};
+
+/// Environment variable the SPIFFE Workload Endpoint specification reserves
+/// for the Workload API address.
+pub const ENDPOINT_SOCKET_ENV: &str = "SPIFFE_ENDPOINT_SOCKET";
+
+/// A Workload API address.
+///
+/// Accepts the URI forms from the SPIFFE Workload Endpoint specification,
+/// `unix:///path/to/socket` (or `unix:/path/to/socket`) and
+/// `tcp://<ip>:<port>`, as well as a bare filesystem path, which is what
+/// `--socket-path` has always taken. Anything that does not start with one
+/// of those schemes is a path, even when it contains a colon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkloadAddress {
+    Unix(PathBuf),
+    Tcp(SocketAddr),
+}
+
+impl WorkloadAddress {
+    pub fn parse(address: &str) -> Result<Self> {
+        parse_address(address)
+            .with_context(|| format!("invalid workload endpoint address {address:?}"))
+    }
+}
+
+impl fmt::Display for WorkloadAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unix(path) => write!(f, "unix://{}", path.display()),
+            Self::Tcp(addr) => write!(f, "tcp://{addr}"),
+        }
+    }
+}
+
+fn parse_address(address: &str) -> Result<WorkloadAddress> {
+    if address.is_empty() {
+        anyhow::bail!("socket path must not be empty");
+    }
+    // Only a known scheme makes the address a URI, so socket paths are free
+    // to contain colons.
+    let (scheme, rest) = match address.split_once(':') {
+        Some((scheme @ ("unix" | "tcp"), rest)) => (scheme, rest),
+        Some((scheme, rest)) if rest.starts_with("//") && !scheme.contains('/') => {
+            anyhow::bail!("URI scheme must be \"unix\" or \"tcp\", not {scheme:?}")
+        }
+        _ => return Ok(WorkloadAddress::Unix(PathBuf::from(address))),
+    };
+    if rest.contains(['?', '#']) {
+        anyhow::bail!("URI must not include a query or fragment");
+    }
+
+    match scheme {
+        "unix" => {
+            let path = match rest.strip_prefix("//") {
+                Some(path) if !path.starts_with('/') => {
+                    anyhow::bail!("unix URI must not include an authority; use unix:///path")
+                }
+                Some(path) => path,
+                None => rest,
+            };
+            if !path.starts_with('/') {
+                anyhow::bail!("unix URI must use an absolute path, e.g. unix:///path");
+            }
+            if path.len() == 1 {
+                anyhow::bail!("unix URI must include a socket path");
+            }
+            Ok(WorkloadAddress::Unix(PathBuf::from(path)))
+        }
+        "tcp" => {
+            let Some(rest) = rest.strip_prefix("//") else {
+                anyhow::bail!("tcp URI must use the form tcp://ip:port");
+            };
+            let authority = rest.strip_suffix('/').unwrap_or(rest);
+            if authority.contains('/') {
+                anyhow::bail!("tcp URI must not include a path");
+            }
+            if authority.contains('@') {
+                anyhow::bail!("tcp URI must not include user info");
+            }
+            // The specification requires an IP literal; names would need DNS.
+            let addr = authority.parse::<SocketAddr>().map_err(|_| {
+                anyhow!("tcp URI host must be an IP address with a port, e.g. tcp://127.0.0.1:8081")
+            })?;
+            Ok(WorkloadAddress::Tcp(addr))
+        }
+        _ => unreachable!("only known schemes reach here"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::WorkloadAddress;
+
+    #[test]
+    fn parse_accepts_spec_forms() {
+        assert_eq!(
+            WorkloadAddress::parse("/tmp/agent.sock").unwrap(),
+            WorkloadAddress::Unix(PathBuf::from("/tmp/agent.sock"))
+        );
+        assert_eq!(
+            WorkloadAddress::parse("agent.sock").unwrap(),
+            WorkloadAddress::Unix(PathBuf::from("agent.sock"))
+        );
+        assert_eq!(
+            WorkloadAddress::parse("/tmp/agent:1.sock").unwrap(),
+            WorkloadAddress::Unix(PathBuf::from("/tmp/agent:1.sock"))
+        );
+        assert_eq!(
+            WorkloadAddress::parse("unix:///tmp/agent.sock").unwrap(),
+            WorkloadAddress::Unix(PathBuf::from("/tmp/agent.sock"))
+        );
+        assert_eq!(
+            WorkloadAddress::parse("unix:/tmp/agent.sock").unwrap(),
+            WorkloadAddress::Unix(PathBuf::from("/tmp/agent.sock"))
+        );
+        assert_eq!(
+            WorkloadAddress::parse("tcp://127.0.0.1:8081").unwrap(),
+            WorkloadAddress::Tcp("127.0.0.1:8081".parse().unwrap())
+        );
+        assert_eq!(
+            WorkloadAddress::parse("tcp://[::1]:8081").unwrap(),
+            WorkloadAddress::Tcp("[::1]:8081".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_addresses() {
+        for address in [
+            "",
+            "unix:tmp/agent.sock",
+            "unix://tmp/agent.sock",
+            "unix://",
+            "unix:///tmp/agent.sock?x=1",
+            "tcp://localhost:8081",
+            "tcp://127.0.0.1",
+            "tcp:127.0.0.1:8081",
+            "tcp://127.0.0.1:8081/path",
+            "tcp://user@127.0.0.1:8081",
+            "http://127.0.0.1:8081",
+        ] {
+            assert!(
+                WorkloadAddress::parse(address).is_err(),
+                "{address:?} should be rejected"
+            );
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
 use anyhow::{Context, Result};
 use hyper_util::rt::TokioIo;
 use tonic::Status;
@@ -7,19 +10,38 @@ use tower::service_fn;
 
 use crate::grpc::spiffe_workload_api_client::SpiffeWorkloadApiClient;
 
+mod address;
+
+pub use address::{ENDPOINT_SOCKET_ENV, WorkloadAddress};
+
 const SECURITY_HEADER_KEY: &str = "workload.spiffe.io";
 const SECURITY_HEADER_VALUE: &str = "true";
 
+type SecurityHeaderInterceptor = fn(tonic::Request<()>) -> Result<tonic::Request<()>, Status>;
+
 pub type WorkloadClient = SpiffeWorkloadApiClient<
-    tonic::service::interceptor::InterceptedService<
-        Channel,
-        fn(tonic::Request<()>) -> Result<tonic::Request<()>, Status>,
-    >,
+    tonic::service::interceptor::InterceptedService<Channel, SecurityHeaderInterceptor>,
 >;
 
+pub type HealthClient = tonic_health::pb::health_client::HealthClient<
+    tonic::service::interceptor::InterceptedService<Channel, SecurityHeaderInterceptor>,
+>;
+
+// The connector is picked from the address scheme, so every command works
+// over either transport.
 pub async fn connect_channel(socket_path: &str) -> Result<Channel> {
-    let socket_path = socket_path.to_string();
-    let channel = Endpoint::try_from("http://[::]:50051")?
+    let address = WorkloadAddress::parse(socket_path)?;
+    let channel = match &address {
+        WorkloadAddress::Unix(path) => connect_unix(path.clone()).await,
+        WorkloadAddress::Tcp(addr) => connect_tcp(*addr).await,
+    }
+    .with_context(|| format!("failed to connect to {address}"))?;
+
+    Ok(channel)
+}
+
+async fn connect_unix(socket_path: PathBuf) -> Result<Channel, tonic::transport::Error> {
+    Endpoint::from_static("http://[::]:50051")
         .connect_with_connector(service_fn(move |_: Uri| {
             let path = socket_path.clone();
             async move {
@@ -28,17 +50,27 @@ pub async fn connect_channel(socket_path: &str) -> Result<Channel> {
             }
         }))
         .await
-        .context("failed to connect to socket")?;
+}
 
-    Ok(channel)
+async fn connect_tcp(addr: SocketAddr) -> Result<Channel, tonic::transport::Error> {
+    // `SocketAddr` formats IPv6 literals in brackets, as URIs require.
+    Endpoint::from_shared(format!("http://{addr}"))?
+        .connect()
+        .await
 }
 
 pub async fn connect_workload_client(socket_path: &str) -> Result<WorkloadClient> {
     let channel = connect_channel(socket_path).await?;
-    Ok(SpiffeWorkloadApiClient::with_interceptor(
-        channel,
-        add_security_header,
-    ))
+    Ok(workload_client(channel))
+}
+
+pub fn workload_client(channel: Channel) -> WorkloadClient {
+    SpiffeWorkloadApiClient::with_interceptor(channel, add_security_header)
+}
+
+// grpc.health.v1 on the Workload API endpoint, sent with the same header.
+pub fn health_client(channel: Channel) -> HealthClient {
+    tonic_health::pb::health_client::HealthClient::with_interceptor(channel, add_security_header)
 }
 
 // interceptor adding security header
@@ -48,4 +80,65 @@ fn add_security_header(mut request: tonic::Request<()>) -> Result<tonic::Request
         MetadataValue::from_static(SECURITY_HEADER_VALUE),
     );
     Ok(request)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tonic::metadata::MetadataValue;
+    use tonic::service::interceptor::InterceptedService;
+    use tonic::transport::Server;
+    use tonic::transport::server::TcpIncoming;
+    use tonic::{Request, Status};
+    use tonic_health::pb::HealthCheckRequest;
+    use tonic_health::pb::health_check_response::ServingStatus;
+
+    use super::{SECURITY_HEADER_KEY, connect_channel, health_client};
+
+    fn require_security_header(request: Request<()>) -> Result<Request<()>, Status> {
+        match request.metadata().get(SECURITY_HEADER_KEY) {
+            Some(value) if value == MetadataValue::from_static("true") => Ok(request),
+            _ => Err(Status::invalid_argument(
+                "security header missing from request",
+            )),
+        }
+    }
+
+    async fn check_over_tcp(listener: TcpListener) {
+        let addr = listener.local_addr().unwrap();
+        let (_reporter, health_service) = tonic_health::server::health_reporter();
+        tokio::spawn(
+            Server::builder()
+                .add_service(InterceptedService::new(
+                    health_service,
+                    require_security_header,
+                ))
+                .serve_with_incoming(TcpIncoming::from(listener)),
+        );
+
+        let channel = connect_channel(&format!("tcp://{addr}")).await.unwrap();
+        let mut client = health_client(channel);
+        let response = client
+            .check(HealthCheckRequest {
+                service: String::new(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().status(), ServingStatus::Serving);
+    }
+
+    #[tokio::test]
+    async fn connect_channel_dials_tcp_with_security_header() {
+        check_over_tcp(TcpListener::bind("127.0.0.1:0").await.unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn connect_channel_dials_ipv6_literals() {
+        // Hosts and containers without IPv6 cannot bind the loopback address.
+        let Ok(listener) = TcpListener::bind("[::1]:0").await else {
+            eprintln!("skipping: IPv6 loopback is not available");
+            return;
+        };
+        check_over_tcp(listener).await;
+    }
+}
@@ -0,0 +1,439 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Serialize, Serializer};
+
+const SCHEME_PREFIX: &str = "spiffe://";
+const MAX_SPIFFE_ID_LENGTH: usize = 2048;
+const MAX_TRUST_DOMAIN_LENGTH: usize = 255;
+
+/// Why a string is not a valid SPIFFE ID or trust domain name. The messages
+/// follow go-spiffe, so that errors read the same as from SPIRE itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpiffeIdError {
+    Empty,
+    WrongScheme,
+    MissingTrustDomain,
+    BadTrustDomainChar,
+    TrustDomainTooLong,
+    QueryOrFragment,
+    EmptySegment,
+    DotSegment,
+    TrailingSlash,
+    BadPathSegmentChar,
+    TooLong,
+}
+
+impl fmt::Display for SpiffeIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Empty => "cannot be empty",
+            Self::WrongScheme => "scheme is missing or invalid",
+            Self::MissingTrustDomain => "trust domain is missing",
+            Self::BadTrustDomainChar => {
+                "trust domain characters are limited to lowercase letters, numbers, dots, \
+                 dashes, and underscores"
+            }
+            Self::TrustDomainTooLong => "trust domain cannot be longer than 255 bytes",
+            Self::QueryOrFragment => "query and fragment are not allowed",
+            Self::EmptySegment => "path cannot contain empty segments",
+            Self::DotSegment => "path cannot contain dot segments",
+            Self::TrailingSlash => "path cannot have a trailing slash",
+            Self::BadPathSegmentChar => {
+                "path segment characters are limited to letters, numbers, dots, dashes, and \
+                 underscores"
+            }
+            Self::TooLong => "SPIFFE ID cannot be longer than 2048 bytes",
+        })
+    }
+}
+
+impl std::error::Error for SpiffeIdError {}
+
+/// The name of a SPIFFE trust domain, such as `example.org`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TrustDomain {
+    name: String,
+}
+
+impl TrustDomain {
+    /// Parses a trust domain name, or takes the trust domain of a SPIFFE ID
+    /// such as `spiffe://example.org`.
+    pub fn new(name_or_id: &str) -> Result<Self, SpiffeIdError> {
+        if name_or_id.contains(":/") {
+            return SpiffeId::new(name_or_id).map(|id| id.trust_domain);
+        }
+        validate_trust_domain(name_or_id)?;
+        Ok(Self {
+            name: name_or_id.to_string(),
+        })
+    }
+
+    /// The name without the `spiffe://` scheme.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The trust domain's own SPIFFE ID, `spiffe://<name>`, which is how the
+    /// Workload API keys bundles.
+    pub fn id(&self) -> SpiffeId {
+        SpiffeId {
+            trust_domain: self.clone(),
+            path: String::new(),
+        }
+    }
+
+    /// The ID of a workload in this trust domain. `path` is either empty or
+    /// starts with a slash, e.g. `/ns/default/sa/web`.
+    pub fn spiffe_id(&self, path: &str) -> Result<SpiffeId, SpiffeIdError> {
+        validate_path(path)?;
+        let id = SpiffeId {
+            trust_domain: self.clone(),
+            path: path.to_string(),
+        };
+        id.check_length()?;
+        Ok(id)
+    }
+}
+
+impl fmt::Display for TrustDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl FromStr for TrustDomain {
+    type Err = SpiffeIdError;
+
+    fn from_str(s: &str) -> Result<Self, SpiffeIdError> {
+        Self::new(s)
+    }
+}
+
+impl PartialEq<str> for TrustDomain {
+    fn eq(&self, other: &str) -> bool {
+        self.name == other
+    }
+}
+
+impl PartialEq<&str> for TrustDomain {
+    fn eq(&self, other: &&str) -> bool {
+        self.name == *other
+    }
+}
+
+impl Serialize for TrustDomain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+/// A SPIFFE ID: `spiffe://<trust domain><path>`, validated against the
+/// SPIFFE ID specification.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpiffeId {
+    trust_domain: TrustDomain,
+    path: String,
+}
+
+impl SpiffeId {
+    pub fn new(id: &str) -> Result<Self, SpiffeIdError> {
+        if id.is_empty() {
+            return Err(SpiffeIdError::Empty);
+        }
+        if id.len() > MAX_SPIFFE_ID_LENGTH {
+            return Err(SpiffeIdError::TooLong);
+        }
+        let rest = id
+            .strip_prefix(SCHEME_PREFIX)
+            .ok_or(SpiffeIdError::WrongScheme)?;
+        let (name, path) = rest.find('/').map_or((rest, ""), |idx| rest.split_at(idx));
+        if name.is_empty() {
+            return Err(SpiffeIdError::MissingTrustDomain);
+        }
+        validate_trust_domain(name)?;
+        validate_path(path)?;
+
+        Ok(Self {
+            trust_domain: TrustDomain {
+                name: name.to_string(),
+            },
+            path: path.to_string(),
+        })
+    }
+
+    /// Builds an ID from path segments, e.g. `["ns", "default"]` for
+    /// `/ns/default`. Each segment is validated on its own, so a segment
+    /// cannot smuggle in a `/`.
+    pub fn from_segments(
+        trust_domain: &TrustDomain,
+        segments: &[&str],
+    ) -> Result<Self, SpiffeIdError> {
+        let mut path = String::new();
+        for segment in segments {
+            validate_path_segment(segment)?;
+            path.push('/');
+            path.push_str(segment);
+        }
+        let id = Self {
+            trust_domain: trust_domain.clone(),
+            path,
+        };
+        id.check_length()?;
+        Ok(id)
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    /// The path, empty or starting with a slash.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Appends a relative path, e.g. `sa/web` to `spiffe://example.org/ns`.
+    pub fn join(&self, path: &str) -> Result<Self, SpiffeIdError> {
+        self.trust_domain
+            .spiffe_id(&format!("{}/{path}", self.path))
+    }
+
+    /// Whether this ID belongs to `trust_domain`.
+    pub fn is_member_of(&self, trust_domain: &TrustDomain) -> bool {
+        self.trust_domain == *trust_domain
+    }
+
+    fn check_length(&self) -> Result<(), SpiffeIdError> {
+        let len = SCHEME_PREFIX.len() + self.trust_domain.name.len() + self.path.len();
+        if len > MAX_SPIFFE_ID_LENGTH {
+            return Err(SpiffeIdError::TooLong);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SpiffeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{SCHEME_PREFIX}{}{}", self.trust_domain, self.path)
+    }
+}
+
+impl FromStr for SpiffeId {
+    type Err = SpiffeIdError;
+
+    fn from_str(s: &str) -> Result<Self, SpiffeIdError> {
+        Self::new(s)
+    }
+}
+
+impl PartialEq<str> for SpiffeId {
+    fn eq(&self, other: &str) -> bool {
+        other
+            .strip_prefix(SCHEME_PREFIX)
+            .and_then(|rest| rest.strip_prefix(self.trust_domain.name.as_str()))
+            .is_some_and(|path| path == self.path)
+    }
+}
+
+impl PartialEq<&str> for SpiffeId {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl Serialize for SpiffeId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+fn validate_trust_domain(name: &str) -> Result<(), SpiffeIdError> {
+    if name.is_empty() {
+        return Err(SpiffeIdError::MissingTrustDomain);
+    }
+    for c in name.chars() {
+        match c {
+            'a'..='z' | '0'..='9' | '.' | '-' | '_' => {}
+            '?' | '#' => return Err(SpiffeIdError::QueryOrFragment),
+            _ => return Err(SpiffeIdError::BadTrustDomainChar),
+        }
+    }
+    if name.len() > MAX_TRUST_DOMAIN_LENGTH {
+        return Err(SpiffeIdError::TrustDomainTooLong);
+    }
+    Ok(())
+}
+
+fn validate_path(path: &str) -> Result<(), SpiffeIdError> {
+    if path.is_empty() {
+        return Ok(());
+    }
+    let Some(segments) = path.strip_prefix('/') else {
+        return Err(SpiffeIdError::BadPathSegmentChar);
+    };
+    if path.contains(['?', '#']) {
+        return Err(SpiffeIdError::QueryOrFragment);
+    }
+
+    let count = segments.split('/').count();
+    for (idx, segment) in (1..).zip(segments.split('/')) {
+        if segment.is_empty() && idx == count {
+            return Err(SpiffeIdError::TrailingSlash);
+        }
+        validate_path_segment(segment)?;
+    }
+    Ok(())
+}
+
+fn validate_path_segment(segment: &str) -> Result<(), SpiffeIdError> {
+    match segment {
+        "" => return Err(SpiffeIdError::EmptySegment),
+        "." | ".." => return Err(SpiffeIdError::DotSegment),
+        _ => {}
+    }
+    if !segment
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+    {
+        return Err(SpiffeIdError::BadPathSegmentChar);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpiffeId, SpiffeIdError, TrustDomain};
+
+    #[test]
+    fn spiffe_id_parses_valid_ids() {
+        for (input, trust_domain, path) in [
+            ("spiffe://trustdomain", "trustdomain", ""),
+            ("spiffe://trustdomain/path", "trustdomain", "/path"),
+            (
+                "spiffe://trust-domain.example_1/Path/to.Workload-1_a",
+                "trust-domain.example_1",
+                "/Path/to.Workload-1_a",
+            ),
+            ("spiffe://td/.../..a/a..", "td", "/.../..a/a.."),
+        ] {
+            let id = SpiffeId::new(input).unwrap_or_else(|err| panic!("{input}: {err}"));
+            assert_eq!(id.trust_domain().name(), trust_domain);
+            assert_eq!(id.path(), path);
+            assert_eq!(id.to_string(), input);
+            assert_eq!(id, input);
+        }
+    }
+
+    // The invalid cases from the SPIFFE ID specification and go-spiffe.
+    #[test]
+    fn spiffe_id_rejects_spec_violations() {
+        let long_path = format!("spiffe://td/{}", "a".repeat(2048));
+        let long_trust_domain = format!("spiffe://{}", "a".repeat(256));
+        for (input, expected) in [
+            ("", SpiffeIdError::Empty),
+            ("trustdomain/path", SpiffeIdError::WrongScheme),
+            ("SPIFFE://trustdomain", SpiffeIdError::WrongScheme),
+            ("spiffe:/trustdomain", SpiffeIdError::WrongScheme),
+            ("https://trustdomain", SpiffeIdError::WrongScheme),
+            ("spiffe://", SpiffeIdError::MissingTrustDomain),
+            ("spiffe:///path", SpiffeIdError::MissingTrustDomain),
+            ("spiffe://Trustdomain", SpiffeIdError::BadTrustDomainChar),
+            (
+                "spiffe://trustdomain:8080",
+                SpiffeIdError::BadTrustDomainChar,
+            ),
+            (
+                "spiffe://user@trustdomain",
+                SpiffeIdError::BadTrustDomainChar,
+            ),
+            ("spiffe://trust%20domain", SpiffeIdError::BadTrustDomainChar),
+            (&long_trust_domain, SpiffeIdError::TrustDomainTooLong),
+            ("spiffe://trustdomain?query", SpiffeIdError::QueryOrFragment),
+            (
+                "spiffe://trustdomain/path?query",
+                SpiffeIdError::QueryOrFragment,
+            ),
+            (
+                "spiffe://trustdomain/path#fragment",
+                SpiffeIdError::QueryOrFragment,
+            ),
+            ("spiffe://trustdomain/", SpiffeIdError::TrailingSlash),
+            ("spiffe://trustdomain/path/", SpiffeIdError::TrailingSlash),
+            ("spiffe://trustdomain//path", SpiffeIdError::EmptySegment),
+            ("spiffe://trustdomain/./path", SpiffeIdError::DotSegment),
+            ("spiffe://trustdomain/path/..", SpiffeIdError::DotSegment),
+            (
+                "spiffe://trustdomain/p%41th",
+                SpiffeIdError::BadPathSegmentChar,
+            ),
+            (
+                "spiffe://trustdomain/path with space",
+                SpiffeIdError::BadPathSegmentChar,
+            ),
+            (
+                "spiffe://trustdomain/pâth",
+                SpiffeIdError::BadPathSegmentChar,
+            ),
+            (&long_path, SpiffeIdError::TooLong),
+        ] {
+            assert_eq!(SpiffeId::new(input), Err(expected), "{input:?}");
+        }
+
+        // Segments are checked one by one, as go-spiffe's FromSegments does.
+        let td = TrustDomain::new("trustdomain").unwrap();
+        let long_segment = "a".repeat(2048);
+        for (segments, expected) in [
+            (&[""][..], SpiffeIdError::EmptySegment),
+            (&["path", ""], SpiffeIdError::EmptySegment),
+            (&["a/b"], SpiffeIdError::BadPathSegmentChar),
+            (&["/path"], SpiffeIdError::BadPathSegmentChar),
+            (&[".."], SpiffeIdError::DotSegment),
+            (&["p%41th"], SpiffeIdError::BadPathSegmentChar),
+            (&[long_segment.as_str()], SpiffeIdError::TooLong),
+        ] {
+            assert_eq!(
+                SpiffeId::from_segments(&td, segments),
+                Err(expected),
+                "{segments:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn trust_domain_accepts_names_and_ids() {
+        let td = TrustDomain::new("example.org").unwrap();
+        assert_eq!(
+            TrustDomain::new("spiffe://example.org/workload"),
+            Ok(td.clone())
+        );
+        assert_eq!(td.id().to_string(), "spiffe://example.org");
+        assert_eq!(TrustDomain::new(""), Err(SpiffeIdError::MissingTrustDomain));
+        assert_eq!(
+            TrustDomain::new("Example.org"),
+            Err(SpiffeIdError::BadTrustDomainChar)
+        );
+        assert_eq!(
+            TrustDomain::new(&"a".repeat(256)),
+            Err(SpiffeIdError::TrustDomainTooLong)
+        );
+    }
+
+    #[test]
+    fn spiffe_id_joins_paths() {
+        let td = TrustDomain::new("example.org").unwrap();
+        let ns = td.spiffe_id("/ns/default").unwrap();
+        assert_eq!(
+            ns.join("sa/web").unwrap(),
+            "spiffe://example.org/ns/default/sa/web"
+        );
+        assert_eq!(
+            SpiffeId::from_segments(&td, &["ns", "default"]).unwrap(),
+            ns
+        );
+        assert_eq!(td.id().join("a").unwrap(), "spiffe://example.org/a");
+        assert_eq!(ns.join("../admin"), Err(SpiffeIdError::DotSegment));
+        assert_eq!(ns.join(""), Err(SpiffeIdError::TrailingSlash));
+        assert_eq!(td.spiffe_id("ns"), Err(SpiffeIdError::BadPathSegmentChar));
+        assert!(ns.is_member_of(&td));
+    }
+}
@@ -0,0 +1,321 @@
+// Test support shared by the library's and the CLI's tests: certificate
+// factories and an in-process Workload API. Streaming calls are served from
+// streams the test queues up front; FetchJWTSVID mints unsigned tokens,
+// counts the calls, and fails with the code the test sets. Everything panics
+// on failure, which is what a test wants.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use rcgen::{
+    BasicConstraints, CertificateParams, CertificateRevocationListParams, DnType,
+    ExtendedKeyUsagePurpose, IsCa, Issuer, KeyIdMethod, KeyPair, KeyUsagePurpose,
+    RevokedCertParams, SanType, SerialNumber, SignatureAlgorithm, date_time_ymd,
+};
+use tokio::sync::{Mutex, mpsc};
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Code, Request, Response, Status};
+
+use crate::grpc::spiffe_workload_api_server::{SpiffeWorkloadApi, SpiffeWorkloadApiServer};
+use crate::grpc::{
+    JwtBundlesRequest, JwtBundlesResponse, Jwtsvid, JwtsvidRequest, JwtsvidResponse,
+    ValidateJwtsvidRequest, ValidateJwtsvidResponse, X509BundlesRequest, X509BundlesResponse,
+    X509svid, X509svidRequest, X509svidResponse,
+};
+use crate::x509::{X509Bundle, X509BundleSet, X509Context, X509Svid};
+
+pub const DEFAULT_SPIFFE_ID: &str = "spiffe://example.org/workload";
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+type Updates<T> = mpsc::Receiver<Result<T, Status>>;
+
+/// The test's end of a running [`FakeAgent`].
+pub struct FakeAgentHandle {
+    pub address: String,
+    pub x509_streams: mpsc::Sender<Updates<X509svidResponse>>,
+    pub jwt_bundle_streams: mpsc::Sender<Updates<JwtBundlesResponse>>,
+    pub jwt_svid_calls: Arc<AtomicUsize>,
+    jwt_svid_error: Arc<std::sync::Mutex<Option<Code>>>,
+}
+
+impl FakeAgentHandle {
+    pub fn jwt_svid_calls(&self) -> usize {
+        self.jwt_svid_calls.load(Ordering::SeqCst)
+    }
+
+    /// Makes every following FetchJWTSVID call fail with `code`, or succeed
+    /// again for `None`.
+    pub fn fail_jwt_svids(&self, code: Option<Code>) {
+        *self.jwt_svid_error.lock().unwrap() = code;
+    }
+}
+
+struct FakeAgent {
+    x509_streams: Mutex<mpsc::Receiver<Updates<X509svidResponse>>>,
+    jwt_bundle_streams: Mutex<mpsc::Receiver<Updates<JwtBundlesResponse>>>,
+    jwt_svid_ttl: Duration,
+    jwt_svid_calls: Arc<AtomicUsize>,
+    jwt_svid_error: Arc<std::sync::Mutex<Option<Code>>>,
+}
+
+/// Serves a fake agent on a loopback port. JWT-SVIDs expire after
+/// `jwt_svid_ttl`.
+pub async fn start_agent(jwt_svid_ttl: Duration) -> FakeAgentHandle {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("tcp://{}", listener.local_addr().unwrap());
+    let (x509_streams, x509_rx) = mpsc::channel(4);
+    let (jwt_bundle_streams, jwt_bundle_rx) = mpsc::channel(4);
+    let jwt_svid_calls = Arc::new(AtomicUsize::new(0));
+    let jwt_svid_error = Arc::new(std::sync::Mutex::new(None));
+    let agent = FakeAgent {
+        x509_streams: Mutex::new(x509_rx),
+        jwt_bundle_streams: Mutex::new(jwt_bundle_rx),
+        jwt_svid_ttl,
+        jwt_svid_calls: Arc::clone(&jwt_svid_calls),
+        jwt_svid_error: Arc::clone(&jwt_svid_error),
+    };
+    tokio::spawn(
+        Server::builder()
+            .add_service(SpiffeWorkloadApiServer::new(agent))
+            .serve_with_incoming(TcpIncoming::from(listener)),
+    );
+
+    FakeAgentHandle {
+        address,
+        x509_streams,
+        jwt_bundle_streams,
+        jwt_svid_calls,
+        jwt_svid_error,
+    }
+}
+
+/// A DER certificate and its DER PKCS#8 private key.
+pub struct TestCert {
+    pub der: Vec<u8>,
+    pub key: Vec<u8>,
+}
+
+/// A CA that issues certificates for tests.
+pub struct TestCa {
+    issuer: Issuer<'static, KeyPair>,
+    der: Vec<u8>,
+}
+
+impl TestCa {
+    /// A self-signed root CA with `name` as its common name. Tests that need
+    /// two unrelated CAs must give them different names.
+    pub fn new(name: &str) -> Self {
+        Self::from_params(ca_params(name, None))
+    }
+
+    /// A self-signed root CA from custom parameters, e.g. a validity period.
+    pub fn from_params(params: CertificateParams) -> Self {
+        let key = KeyPair::generate().unwrap();
+        let der = params.self_signed(&key).unwrap().der().to_vec();
+        Self {
+            issuer: Issuer::new(params, key),
+            der,
+        }
+    }
+
+    /// An intermediate CA signed by this one.
+    pub fn intermediate(&self, name: &str, path_len: Option<u8>) -> Self {
+        let params = ca_params(name, path_len);
+        let cert = self.issue(params.clone());
+        Self {
+            issuer: Issuer::new(params, KeyPair::try_from(cert.key.as_slice()).unwrap()),
+            der: cert.der,
+        }
+    }
+
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// Signs a certificate for a fresh key.
+    pub fn issue(&self, params: CertificateParams) -> TestCert {
+        let key = KeyPair::generate().unwrap();
+        TestCert {
+            der: params.signed_by(&key, &self.issuer).unwrap().der().to_vec(),
+            key: key.serialize_der(),
+        }
+    }
+
+    /// An X.509-SVID for `spiffe_id`, issued directly by this CA.
+    pub fn svid(&self, spiffe_id: &str) -> X509Svid {
+        let leaf = self.issue(leaf_params(spiffe_id));
+        X509Svid::new(spiffe_id.parse().unwrap(), vec![leaf.der], leaf.key).unwrap()
+    }
+
+    /// A context with one SVID for `spiffe_id` and this CA as the bundle of
+    /// its trust domain.
+    pub fn context(&self, spiffe_id: &str) -> X509Context {
+        let svid = self.svid(spiffe_id);
+        let mut bundles = X509BundleSet::new();
+        bundles.insert(self.bundle(svid.trust_domain().name()));
+        X509Context::new(vec![svid], bundles)
+    }
+
+    /// A DER CRL signed by this CA that revokes `serials`.
+    pub fn crl(&self, serials: &[SerialNumber]) -> Vec<u8> {
+        let revoked_certs = serials
+            .iter()
+            .map(|serial| RevokedCertParams {
+                serial_number: serial.clone(),
+                revocation_time: date_time_ymd(2024, 1, 1),
+                reason_code: None,
+                invalidity_date: None,
+            })
+            .collect();
+        let params = CertificateRevocationListParams {
+            this_update: date_time_ymd(2024, 1, 1),
+            next_update: date_time_ymd(2124, 1, 1),
+            crl_number: SerialNumber::from(1u64),
+            issuing_distribution_point: None,
+            revoked_certs,
+            key_identifier_method: KeyIdMethod::Sha256,
+        };
+        params.signed_by(&self.issuer).unwrap().der().to_vec()
+    }
+
+    /// A bundle holding only this CA.
+    pub fn bundle(&self, trust_domain: &str) -> X509Bundle {
+        X509Bundle::new(trust_domain.parse().unwrap(), vec![self.der.clone()]).unwrap()
+    }
+}
+
+pub fn ca_params(name: &str, path_len: Option<u8>) -> CertificateParams {
+    let mut params = CertificateParams::default();
+    params.distinguished_name.push(DnType::CommonName, name);
+    params.is_ca = IsCa::Ca(match path_len {
+        Some(len) => BasicConstraints::Constrained(len),
+        None => BasicConstraints::Unconstrained,
+    });
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    params
+}
+
+/// Parameters for an end-entity certificate with `uri` as its only URI SAN,
+/// usable for both TLS servers and clients.
+pub fn leaf_params(uri: &str) -> CertificateParams {
+    let mut params = CertificateParams::default();
+    params.is_ca = IsCa::ExplicitNoCa;
+    params.subject_alt_names = vec![SanType::URI(uri.try_into().unwrap())];
+    params.extended_key_usages = vec![
+        ExtendedKeyUsagePurpose::ServerAuth,
+        ExtendedKeyUsagePurpose::ClientAuth,
+    ];
+    params
+}
+
+/// A self-signed certificate with an ECDSA P-256 key.
+pub fn self_signed(params: CertificateParams) -> TestCert {
+    self_signed_with(params, &rcgen::PKCS_ECDSA_P256_SHA256)
+}
+
+pub fn self_signed_with(params: CertificateParams, alg: &'static SignatureAlgorithm) -> TestCert {
+    let key = KeyPair::generate_for(alg).unwrap();
+    TestCert {
+        der: params.self_signed(&key).unwrap().der().to_vec(),
+        key: key.serialize_der(),
+    }
+}
+
+/// A wire-format X.509-SVID with a fresh self-signed certificate, which also
+/// serves as its bundle.
+pub fn x509_svid(spiffe_id: &str, hint: &str) -> X509svid {
+    let cert = self_signed(leaf_params(spiffe_id));
+    X509svid {
+        spiffe_id: spiffe_id.to_string(),
+        x509_svid: cert.der.clone(),
+        x509_svid_key: cert.key,
+        bundle: cert.der,
+        hint: hint.to_string(),
+    }
+}
+
+// Whole seconds round the expiry down, so the token may live up to a second
+// less than `ttl`.
+fn jwt_token(spiffe_id: &str, audience: &[String], ttl: Duration) -> String {
+    let exp = chrono::Utc::now().timestamp() + ttl.as_secs() as i64;
+    let claims = serde_json::json!({ "sub": spiffe_id, "aud": audience, "exp": exp });
+    format!(
+        "{}.{}.c2ln",
+        BASE64_URL.encode(br#"{"alg":"ES256"}"#),
+        BASE64_URL.encode(claims.to_string())
+    )
+}
+
+fn stream<T: Send + 'static>(updates: Option<Updates<T>>) -> Result<ResponseStream<T>, Status> {
+    let updates = updates.ok_or_else(|| Status::unavailable("agent is shutting down"))?;
+    Ok(Box::pin(ReceiverStream::new(updates)))
+}
+
+#[tonic::async_trait]
+impl SpiffeWorkloadApi for FakeAgent {
+    type FetchX509SVIDStream = ResponseStream<X509svidResponse>;
+    type FetchX509BundlesStream = ResponseStream<X509BundlesResponse>;
+    type FetchJWTBundlesStream = ResponseStream<JwtBundlesResponse>;
+
+    async fn fetch_x509svid(
+        &self,
+        _: Request<X509svidRequest>,
+    ) -> Result<Response<Self::FetchX509SVIDStream>, Status> {
+        let updates = self.x509_streams.lock().await.recv().await;
+        Ok(Response::new(stream(updates)?))
+    }
+
+    async fn fetch_x509_bundles(
+        &self,
+        _: Request<X509BundlesRequest>,
+    ) -> Result<Response<Self::FetchX509BundlesStream>, Status> {
+        Err(Status::unimplemented("not implemented"))
+    }
+
+    async fn fetch_jwtsvid(
+        &self,
+        request: Request<JwtsvidRequest>,
+    ) -> Result<Response<JwtsvidResponse>, Status> {
+        self.jwt_svid_calls.fetch_add(1, Ordering::SeqCst);
+        // Slow enough for concurrent callers to overlap.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        if let Some(code) = *self.jwt_svid_error.lock().unwrap() {
+            return Err(Status::new(code, "injected failure"));
+        }
+
+        let request = request.into_inner();
+        let spiffe_id = match request.spiffe_id.as_str() {
+            "" => DEFAULT_SPIFFE_ID,
+            spiffe_id => spiffe_id,
+        };
+        Ok(Response::new(JwtsvidResponse {
+            svids: vec![Jwtsvid {
+                spiffe_id: spiffe_id.to_string(),
+                svid: jwt_token(spiffe_id, &request.audience, self.jwt_svid_ttl),
+                hint: String::new(),
+            }],
+        }))
+    }
+
+    async fn fetch_jwt_bundles(
+        &self,
+        _: Request<JwtBundlesRequest>,
+    ) -> Result<Response<Self::FetchJWTBundlesStream>, Status> {
+        let updates = self.jwt_bundle_streams.lock().await.recv().await;
+        Ok(Response::new(stream(updates)?))
+    }
+
+    async fn validate_jwtsvid(
+        &self,
+        _: Request<ValidateJwtsvidRequest>,
+    ) -> Result<Response<ValidateJwtsvidResponse>, Status> {
+        Err(Status::unimplemented("not implemented"))
+    }
+}
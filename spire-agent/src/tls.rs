@@ -0,0 +1,656 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use const_oid::AssociatedOid;
+use der::Decode;
+use rustls::client::ResolvesClientCert;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::WebPkiSupportedAlgorithms;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, DistinguishedName, OtherError,
+    ServerConfig, SignatureScheme,
+};
+use webpki::{
+    BorrowedCertRevocationList, CertRevocationList, EndEntityCert, ExpirationPolicy, KeyUsage,
+    RevocationCheckDepth, RevocationOptionsBuilder, UnknownStatusPolicy,
+};
+use x509_cert::Certificate;
+use x509_cert::ext::pkix::SubjectAltName;
+use x509_cert::ext::pkix::name::GeneralName;
+
+use crate::spiffe_id::{SpiffeId, TrustDomain};
+use crate::x509::X509Context;
+use crate::x509_source::{SvidSelector, X509ContextUpdates};
+
+/// Where the TLS configs read the current SVIDs and bundles from. Every
+/// handshake asks again, so rotations apply to existing configs.
+pub trait X509ContextProvider: fmt::Debug + Send + Sync {
+    /// The latest context, or `None` before the first one arrives.
+    fn x509_context(&self) -> Option<Arc<X509Context>>;
+}
+
+impl X509ContextProvider for X509ContextUpdates {
+    fn x509_context(&self) -> Option<Arc<X509Context>> {
+        self.latest()
+    }
+}
+
+/// A fixed context, e.g. from a single `fetch_x509_context` call.
+impl X509ContextProvider for Arc<X509Context> {
+    fn x509_context(&self) -> Option<Arc<X509Context>> {
+        Some(Arc::clone(self))
+    }
+}
+
+/// Decides which peers may connect, by the SPIFFE ID in their X.509-SVID.
+///
+/// The peer's certificate chain has always been verified against the bundle
+/// of its trust domain before the authorizer is asked.
+#[derive(Clone)]
+pub enum Authorizer {
+    /// Any SPIFFE ID from a trust domain with a bundle.
+    Any,
+    /// Exactly these SPIFFE IDs.
+    Ids(Vec<SpiffeId>),
+    /// Any SPIFFE ID in this trust domain.
+    MemberOf(TrustDomain),
+    /// Whatever the function accepts.
+    Custom(Arc<dyn Fn(&SpiffeId) -> bool + Send + Sync>),
+}
+
+impl Authorizer {
+    pub fn ids(ids: impl IntoIterator<Item = SpiffeId>) -> Self {
+        Self::Ids(ids.into_iter().collect())
+    }
+
+    pub fn member_of(trust_domain: TrustDomain) -> Self {
+        Self::MemberOf(trust_domain)
+    }
+
+    pub fn custom(authorize: impl Fn(&SpiffeId) -> bool + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(authorize))
+    }
+
+    pub fn authorize(&self, spiffe_id: &SpiffeId) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Ids(ids) => ids.contains(spiffe_id),
+            Self::MemberOf(trust_domain) => spiffe_id.is_member_of(trust_domain),
+            Self::Custom(authorize) => authorize(spiffe_id),
+        }
+    }
+}
+
+impl fmt::Debug for Authorizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => f.write_str("Any"),
+            Self::Ids(ids) => f.debug_tuple("Ids").field(ids).finish(),
+            Self::MemberOf(trust_domain) => f.debug_tuple("MemberOf").field(trust_domain).finish(),
+            Self::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// A server config for mutual TLS: presents the SVID `selector` picks from
+/// the current context and requires clients to present one that
+/// `authorizer` accepts.
+pub fn server_config(
+    provider: impl X509ContextProvider + 'static,
+    selector: SvidSelector,
+    authorizer: Authorizer,
+) -> Result<ServerConfig> {
+    let provider: Arc<dyn X509ContextProvider> = Arc::new(provider);
+    let crypto = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = SpiffeVerifier::new(Arc::clone(&provider), authorizer);
+
+    Ok(ServerConfig::builder_with_provider(crypto)
+        .with_safe_default_protocol_versions()
+        .context("failed to configure TLS protocol versions")?
+        .with_client_cert_verifier(Arc::new(verifier))
+        .with_cert_resolver(Arc::new(
+            SvidResolver::new(provider).with_selector(selector),
+        )))
+}
+
+/// A client config for mutual TLS: presents the SVID `selector` picks from
+/// the current context and accepts servers whose SVID `authorizer` accepts.
+/// The server name passed when connecting is not checked; only the SPIFFE ID
+/// is.
+pub fn client_config(
+    provider: impl X509ContextProvider + 'static,
+    selector: SvidSelector,
+    authorizer: Authorizer,
+) -> Result<ClientConfig> {
+    let provider: Arc<dyn X509ContextProvider> = Arc::new(provider);
+    let crypto = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = SpiffeVerifier::new(Arc::clone(&provider), authorizer);
+
+    Ok(ClientConfig::builder_with_provider(crypto)
+        .with_safe_default_protocol_versions()
+        .context("failed to configure TLS protocol versions")?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_client_cert_resolver(Arc::new(
+            SvidResolver::new(provider).with_selector(selector),
+        )))
+}
+
+/// The SPIFFE ID in the URI SAN of a DER certificate, e.g. one of
+/// `peer_certificates()` after a handshake.
+pub fn spiffe_id_from_cert(der: &[u8]) -> Result<SpiffeId> {
+    let cert = Certificate::from_der(der).context("failed to parse certificate")?;
+    let san = match cert
+        .tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|ext| ext.extn_id == SubjectAltName::OID)
+    {
+        Some(ext) => SubjectAltName::from_der(ext.extn_value.as_bytes())
+            .context("failed to parse subject alternative names")?,
+        None => anyhow::bail!("certificate has no URI SAN"),
+    };
+
+    let uris: Vec<String> = san
+        .0
+        .into_iter()
+        .filter_map(|name| match name {
+            GeneralName::UniformResourceIdentifier(uri) => Some(uri.to_string()),
+            _ => None,
+        })
+        .collect();
+    match <[String; 1]>::try_from(uris) {
+        Ok([uri]) => SpiffeId::new(&uri).with_context(|| format!("invalid SPIFFE ID {uri:?}")),
+        Err(uris) if uris.is_empty() => anyhow::bail!("certificate has no URI SAN"),
+        Err(uris) => anyhow::bail!("certificate has {} URI SANs, expected one", uris.len()),
+    }
+}
+
+/// Presents the SVID picked by a [`SvidSelector`] from the latest context,
+/// as a server certificate or a client certificate.
+#[derive(Debug)]
+pub struct SvidResolver {
+    provider: Arc<dyn X509ContextProvider>,
+    selector: SvidSelector,
+    // The key for the context it was built from; rebuilt after a rotation.
+    cached: Mutex<Option<(Arc<X509Context>, Arc<CertifiedKey>)>>,
+}
+
+impl SvidResolver {
+    pub fn new(provider: Arc<dyn X509ContextProvider>) -> Self {
+        Self {
+            provider,
+            selector: SvidSelector::Default,
+            cached: Mutex::new(None),
+        }
+    }
+
+    pub fn with_selector(mut self, selector: SvidSelector) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    fn certified_key(&self) -> Option<Arc<CertifiedKey>> {
+        let context = self.provider.x509_context()?;
+        let mut cached = self.cached.lock().unwrap();
+        if let Some((built_from, key)) = cached.as_ref()
+            && Arc::ptr_eq(built_from, &context)
+        {
+            return Some(Arc::clone(key));
+        }
+
+        let svid = self.selector.select(&context)?;
+        let chain = svid
+            .cert_chain()
+            .iter()
+            .map(|cert| CertificateDer::from(cert.clone()))
+            .collect();
+        let der = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(svid.private_key().to_vec()));
+        // A key rustls cannot use leaves the handshake without a certificate,
+        // which fails it on the peer's side.
+        let signing_key = rustls::crypto::ring::sign::any_supported_type(&der).ok()?;
+        let key = Arc::new(CertifiedKey::new(chain, signing_key));
+        cached.replace((context, Arc::clone(&key)));
+        Some(key)
+    }
+}
+
+impl ResolvesServerCert for SvidResolver {
+    fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.certified_key()
+    }
+}
+
+impl ResolvesClientCert for SvidResolver {
+    fn resolve(&self, _: &[&[u8]], _: &[SignatureScheme]) -> Option<Arc<CertifiedKey>> {
+        self.certified_key()
+    }
+
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
+
+/// Verifies a peer's X.509-SVID against the latest bundle of its trust
+/// domain, then asks an [`Authorizer`] about its SPIFFE ID. Host names are
+/// not checked.
+///
+/// Certificates revoked by one of the context's CRLs are rejected anywhere in
+/// the chain. Certificates whose issuer has no CRL in the context pass, and
+/// so do CRLs past their next update, since the agent only publishes what
+/// its trust domain has.
+#[derive(Debug)]
+pub struct SpiffeVerifier {
+    provider: Arc<dyn X509ContextProvider>,
+    authorizer: Authorizer,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl SpiffeVerifier {
+    pub fn new(provider: Arc<dyn X509ContextProvider>, authorizer: Authorizer) -> Self {
+        Self {
+            provider,
+            authorizer,
+            algorithms: rustls::crypto::ring::default_provider().signature_verification_algorithms,
+        }
+    }
+
+    fn verify_peer(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+        usage: KeyUsage,
+    ) -> Result<(), rustls::Error> {
+        let context = self
+            .provider
+            .x509_context()
+            .ok_or_else(|| rustls::Error::General("no X509 bundles received yet".to_string()))?;
+        let spiffe_id =
+            spiffe_id_from_cert(end_entity).map_err(|err| other_error(format!("{err:#}")))?;
+        let bundle = context
+            .bundles()
+            .get(spiffe_id.trust_domain())
+            .ok_or(CertificateError::UnknownIssuer)?;
+
+        let authorities: Vec<CertificateDer<'_>> = bundle
+            .authorities()
+            .iter()
+            .map(|cert| CertificateDer::from(cert.as_slice()))
+            .collect();
+        let anchors = authorities
+            .iter()
+            .map(webpki::anchor_from_trusted_cert)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(pki_error)?;
+        // Contexts only hold CRLs that parsed when they arrived.
+        let crls: Vec<CertRevocationList<'_>> = context
+            .bundles()
+            .crls()
+            .iter()
+            .filter_map(|der| BorrowedCertRevocationList::from_der(der).ok())
+            .map(CertRevocationList::from)
+            .collect();
+        let crls: Vec<&CertRevocationList<'_>> = crls.iter().collect();
+        let revocation = RevocationOptionsBuilder::new(&crls).ok().map(|builder| {
+            builder
+                .with_depth(RevocationCheckDepth::Chain)
+                .with_status_policy(UnknownStatusPolicy::Allow)
+                .with_expiration_policy(ExpirationPolicy::Ignore)
+                .build()
+        });
+
+        EndEntityCert::try_from(end_entity)
+            .map_err(pki_error)?
+            .verify_for_usage(
+                self.algorithms.all,
+                &anchors,
+                intermediates,
+                now,
+                usage,
+                revocation,
+                None,
+            )
+            .map_err(pki_error)?;
+
+        if !self.authorizer.authorize(&spiffe_id) {
+            return Err(other_error(format!("peer {spiffe_id} is not authorized")));
+        }
+        Ok(())
+    }
+}
+
+impl ServerCertVerifier for SpiffeVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _: &ServerName<'_>,
+        _: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.verify_peer(end_entity, intermediates, now, KeyUsage::server_auth())?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+impl ClientCertVerifier for SpiffeVerifier {
+    // SVIDs of different trust domains may be issued by any CA, so there is
+    // nothing useful to hint.
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.verify_peer(end_entity, intermediates, now, KeyUsage::client_auth())?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+fn other_error(message: String) -> rustls::Error {
+    let err: Box<dyn StdError + Send + Sync> = message.into();
+    CertificateError::Other(OtherError(Arc::from(err))).into()
+}
+
+// The common WebPKI failures keep their rustls names, so that alerts and
+// logs read the same as with the stock verifiers.
+fn pki_error(err: webpki::Error) -> rustls::Error {
+    match err {
+        webpki::Error::BadDer | webpki::Error::BadDerTime => CertificateError::BadEncoding.into(),
+        webpki::Error::CertExpired { .. } => CertificateError::Expired.into(),
+        webpki::Error::CertNotValidYet { .. } => CertificateError::NotValidYet.into(),
+        webpki::Error::UnknownIssuer => CertificateError::UnknownIssuer.into(),
+        webpki::Error::CertRevoked => CertificateError::Revoked.into(),
+        webpki::Error::InvalidSignatureForPublicKey => CertificateError::BadSignature.into(),
+        err => CertificateError::Other(OtherError(Arc::new(err))).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use rustls::client::Resumption;
+    use rustls::pki_types::{CertificateDer, ServerName};
+    use rustls::{
+        CertificateError, ClientConfig, ClientConnection, ServerConfig, ServerConnection,
+    };
+
+    use super::{
+        Authorizer, X509ContextProvider, client_config, server_config, spiffe_id_from_cert,
+    };
+    use crate::grpc::{X509svid, X509svidResponse};
+    use crate::spiffe_id::SpiffeId;
+    use crate::testing::{TestCa, TestCert, leaf_params};
+    use crate::x509::{X509BundleSet, X509Context};
+    use crate::x509_source::SvidSelector;
+
+    fn context(ca: &TestCa, spiffe_id: &str) -> Arc<X509Context> {
+        Arc::new(ca.context(spiffe_id))
+    }
+
+    // A context with `leaf` as its SVID, as the agent would send it with
+    // `crls`.
+    fn context_with_crls(
+        ca: &TestCa,
+        spiffe_id: &str,
+        leaf: TestCert,
+        crls: Vec<Vec<u8>>,
+    ) -> Arc<X509Context> {
+        let response = X509svidResponse {
+            svids: vec![X509svid {
+                spiffe_id: spiffe_id.to_string(),
+                x509_svid: leaf.der,
+                x509_svid_key: leaf.key,
+                bundle: ca.der().to_vec(),
+                hint: String::new(),
+            }],
+            crl: crls,
+            ..Default::default()
+        };
+        Arc::new(X509Context::try_from(&response).unwrap())
+    }
+
+    // Lets a test rotate the context under a running config.
+    #[derive(Debug, Clone)]
+    struct Rotating(Arc<Mutex<Arc<X509Context>>>);
+
+    impl X509ContextProvider for Rotating {
+        fn x509_context(&self) -> Option<Arc<X509Context>> {
+            Some(Arc::clone(&self.0.lock().unwrap()))
+        }
+    }
+
+    fn configs(
+        client: impl X509ContextProvider + 'static,
+        server: impl X509ContextProvider + 'static,
+        authorizer: Authorizer,
+    ) -> (Arc<ClientConfig>, Arc<ServerConfig>) {
+        let mut client_config = client_config(client, SvidSelector::Default, authorizer).unwrap();
+        // Resumed sessions skip the certificates, which the tests look at.
+        client_config.resumption = Resumption::disabled();
+        (
+            Arc::new(client_config),
+            Arc::new(server_config(server, SvidSelector::Default, Authorizer::Any).unwrap()),
+        )
+    }
+
+    // Runs a handshake in memory and returns the SPIFFE IDs each side saw.
+    fn handshake(
+        (client_config, server_config): &(Arc<ClientConfig>, Arc<ServerConfig>),
+    ) -> Result<(SpiffeId, SpiffeId), rustls::Error> {
+        let name = ServerName::try_from("workload").unwrap();
+        let mut client = ClientConnection::new(Arc::clone(client_config), name).unwrap();
+        let mut server = ServerConnection::new(Arc::clone(server_config)).unwrap();
+
+        while client.is_handshaking() || server.is_handshaking() {
+            let mut buf = Vec::new();
+            client.write_tls(&mut buf).unwrap();
+            server.read_tls(&mut buf.as_slice()).unwrap();
+            server.process_new_packets()?;
+
+            let mut buf = Vec::new();
+            server.write_tls(&mut buf).unwrap();
+            client.read_tls(&mut buf.as_slice()).unwrap();
+            client.process_new_packets()?;
+        }
+
+        let peer_id =
+            |certs: Option<&[CertificateDer<'_>]>| spiffe_id_from_cert(&certs.unwrap()[0]).unwrap();
+        Ok((
+            peer_id(client.peer_certificates()),
+            peer_id(server.peer_certificates()),
+        ))
+    }
+
+    #[test]
+    fn mutual_tls_authenticates_both_sides_by_spiffe_id() {
+        let ca = TestCa::new("example.org CA");
+        let client = context(&ca, "spiffe://example.org/client");
+        let server = context(&ca, "spiffe://example.org/server");
+
+        let authorizer = Authorizer::ids(["spiffe://example.org/server".parse().unwrap()]);
+        let (seen_by_client, seen_by_server) = handshake(&configs(
+            Arc::clone(&client),
+            Arc::clone(&server),
+            authorizer,
+        ))
+        .unwrap();
+        assert_eq!(seen_by_client, "spiffe://example.org/server");
+        assert_eq!(seen_by_server, "spiffe://example.org/client");
+
+        let authorizer = Authorizer::member_of("other.org".parse().unwrap());
+        let err = handshake(&configs(Arc::clone(&client), server, authorizer)).unwrap_err();
+        assert!(err.to_string().contains("is not authorized"), "{err}");
+
+        // A server whose SVID chains to a CA the client does not trust.
+        let untrusted = context(&TestCa::new("impostor CA"), "spiffe://example.org/server");
+        let err = handshake(&configs(client, untrusted, Authorizer::Any)).unwrap_err();
+        assert_eq!(
+            err,
+            rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer)
+        );
+    }
+
+    #[test]
+    fn server_authorizes_clients_by_spiffe_id() {
+        let ca = TestCa::new("example.org CA");
+        let server = context(&ca, "spiffe://example.org/server");
+        let authorizer = Authorizer::ids(["spiffe://example.org/allowed".parse().unwrap()]);
+        let server_config = Arc::new(
+            server_config(Arc::clone(&server), SvidSelector::Default, authorizer).unwrap(),
+        );
+        let client_config = |spiffe_id: &str| {
+            let mut config = client_config(
+                context(&ca, spiffe_id),
+                SvidSelector::Default,
+                Authorizer::Any,
+            )
+            .unwrap();
+            config.resumption = Resumption::disabled();
+            Arc::new(config)
+        };
+
+        let configs = (
+            client_config("spiffe://example.org/allowed"),
+            Arc::clone(&server_config),
+        );
+        assert_eq!(
+            handshake(&configs).unwrap().1,
+            "spiffe://example.org/allowed"
+        );
+
+        let configs = (client_config("spiffe://example.org/other"), server_config);
+        let err = handshake(&configs).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("peer spiffe://example.org/other is not authorized"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn mutual_tls_presents_rotated_svids() {
+        let ca = TestCa::new("example.org CA");
+        let server = Rotating(Arc::new(Mutex::new(context(&ca, "spiffe://example.org/a"))));
+        let client = context(&ca, "spiffe://example.org/client");
+        let configs = configs(client, server.clone(), Authorizer::Any);
+
+        assert_eq!(handshake(&configs).unwrap().0, "spiffe://example.org/a");
+        *server.0.lock().unwrap() = context(&ca, "spiffe://example.org/b");
+        assert_eq!(handshake(&configs).unwrap().0, "spiffe://example.org/b");
+    }
+
+    #[test]
+    fn mutual_tls_rejects_revoked_svids() {
+        let ca = TestCa::new("example.org CA");
+        let mut params = leaf_params("spiffe://example.org/server");
+        params.serial_number = Some(7u64.into());
+        let server =
+            context_with_crls(&ca, "spiffe://example.org/server", ca.issue(params), vec![]);
+        let client_leaf = || ca.issue(leaf_params("spiffe://example.org/client"));
+
+        // A CRL that lists other serials does not get in the way.
+        let client = context_with_crls(
+            &ca,
+            "spiffe://example.org/client",
+            client_leaf(),
+            vec![ca.crl(&[8u64.into()])],
+        );
+        handshake(&configs(client, Arc::clone(&server), Authorizer::Any)).unwrap();
+
+        let client = context_with_crls(
+            &ca,
+            "spiffe://example.org/client",
+            client_leaf(),
+            vec![ca.crl(&[7u64.into()])],
+        );
+        let err = handshake(&configs(client, server, Authorizer::Any)).unwrap_err();
+        assert_eq!(
+            err,
+            rustls::Error::InvalidCertificate(CertificateError::Revoked)
+        );
+    }
+
+    #[test]
+    fn mutual_tls_presents_the_selected_svid() {
+        let ca = TestCa::new("example.org CA");
+        let mut bundles = X509BundleSet::new();
+        bundles.insert(ca.bundle("example.org"));
+        let server = X509Context::new(
+            vec![
+                ca.svid("spiffe://example.org/a"),
+                ca.svid("spiffe://example.org/b"),
+            ],
+            bundles,
+        );
+        let selector = SvidSelector::SpiffeId("spiffe://example.org/b".parse().unwrap());
+        let server_config = server_config(Arc::new(server), selector, Authorizer::Any).unwrap();
+        let client_config = client_config(
+            context(&ca, "spiffe://example.org/client"),
+            SvidSelector::Default,
+            Authorizer::Any,
+        )
+        .unwrap();
+
+        let (seen_by_client, _) =
+            handshake(&(Arc::new(client_config), Arc::new(server_config))).unwrap();
+        assert_eq!(seen_by_client, "spiffe://example.org/b");
+    }
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use spire_agent::{ValidatedJwtSvid, WorkloadApiClient};
+use tonic::{Code, Status};
+
+use crate::output::{OutputFormat, print_json};
+use crate::retry::{RetryPolicy, retry};
+
+pub async fn validate_jwt(
+    socket_path: &str,
+    timeout: Duration,
+    retry_policy: &RetryPolicy,
+    silent: bool,
+    output: OutputFormat,
+    audience: &str,
+    svid: &str,
+) -> Result<()> {
+    let validated = retry(retry_policy, || async {
+        let mut client = WorkloadApiClient::connect(socket_path).await?;
+        tokio::time::timeout(timeout, client.validate_jwt_svid(svid, audience))
+            .await
+            .context("request timed out")?
+            .map_err(validation_error)
+    })
+    .await?;
+
+    if silent {
+        return Ok(());
+    }
+    match output {
+        OutputFormat::Pretty => print_validation(&validated),
+        OutputFormat::Json => print_json(&ValidateJwtsvidResponseJson::from(&validated)),
+    }
+}
+
+// Keeps the status in the error chain so transient failures are retried.
+fn validation_error(err: anyhow::Error) -> anyhow::Error {
+    let Some(status) = err.downcast_ref::<Status>() else {
+        return err;
+    };
+    let message = match status.code() {
+        Code::InvalidArgument => format!("SVID is not valid: {}", status.message()),
+        _ => format!("unable to validate JWT SVID: {}", status.message()),
+    };
+    err.context(message)
+}
+
+fn print_validation(validated: &ValidatedJwtSvid) -> Result<()> {
+    let claims =
+        serde_json::to_string_pretty(validated.claims()).context("failed to encode claims")?;
+
+    println!("SVID is valid.");
+    println!("SPIFFE ID : {}", validated.spiffe_id());
+    println!("Claims    : {claims}");
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct ValidateJwtsvidResponseJson {
+    spiffe_id: String,
+    claims: Value,
+}
+
+impl From<&ValidatedJwtSvid> for ValidateJwtsvidResponseJson {
+    fn from(validated: &ValidatedJwtSvid) -> Self {
+        Self {
+            spiffe_id: validated.spiffe_id().to_string(),
+            claims: Value::Object(validated.claims().clone()),
+        }
+    }
+}
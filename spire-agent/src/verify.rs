@@ -0,0 +1,412 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use const_oid::db::rfc5912::{
+    ECDSA_WITH_SHA_256, ECDSA_WITH_SHA_384, ID_EC_PUBLIC_KEY, RSA_ENCRYPTION, SECP_256_R_1,
+    SECP_384_R_1, SHA_256_WITH_RSA_ENCRYPTION, SHA_384_WITH_RSA_ENCRYPTION,
+    SHA_512_WITH_RSA_ENCRYPTION,
+};
+use const_oid::db::rfc8410::ID_ED_25519;
+use der::Encode;
+use ring::rand::SystemRandom;
+use ring::signature::{
+    self, EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, UnparsedPublicKey,
+    VerificationAlgorithm,
+};
+use x509_cert::Certificate;
+use x509_cert::ext::pkix::name::GeneralName;
+use spire_agent::{SpiffeId, X509Bundle, X509Context, X509Svid};
+use x509_cert::ext::pkix::{BasicConstraints, KeyUsage, SubjectAltName};
+
+use crate::fetch_x509::{parse_certs, parse_x509_time, svid_bundle};
+use crate::inspect::find_extension;
+use crate::jwk::ec_curve;
+
+/// Outcome of a single verification step.
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    pub result: Result<(), String>,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, result: Result<()>) -> Self {
+        Self {
+            name: name.into(),
+            result: result.map_err(|err| format!("{err:#}")),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Prints the verification report for every SVID and fails if any check did.
+pub fn verify_svids(context: &X509Context, silent: bool) -> Result<()> {
+    let now = Utc::now();
+    let mut failed = 0;
+
+    for svid in context.svids() {
+        let checks = verify_svid(svid, svid_bundle(context, svid)?, now);
+        failed += checks.iter().filter(|check| !check.passed()).count();
+
+        if silent {
+            continue;
+        }
+        println!("Verifying SVID {}:", svid.spiffe_id());
+        for check in &checks {
+            match &check.result {
+                Ok(()) => println!("  [PASS] {}", check.name),
+                Err(err) => println!("  [FAIL] {}: {}", check.name, err),
+            }
+        }
+        println!();
+    }
+
+    if failed > 0 {
+        anyhow::bail!("SVID verification failed: {failed} check(s) did not pass");
+    }
+    Ok(())
+}
+
+pub fn verify_svid(svid: &X509Svid, bundle: &X509Bundle, now: DateTime<Utc>) -> Vec<Check> {
+    let chain = match parse_certs(svid.cert_chain()) {
+        Ok(chain) if !chain.is_empty() => chain,
+        Ok(_) => {
+            return vec![Check::new(
+                "parse SVID chain",
+                Err(anyhow!("chain is empty")),
+            )];
+        }
+        Err(err) => return vec![Check::new("parse SVID chain", Err(err))],
+    };
+    let bundle = match parse_certs(bundle.authorities()) {
+        Ok(bundle) => bundle,
+        Err(err) => return vec![Check::new("parse bundle", Err(err))],
+    };
+
+    let mut checks = Vec::new();
+
+    // Signature path: leaf -> intermediates -> a root in the bundle.
+    for (idx, pair) in chain.windows(2).enumerate() {
+        checks.push(Check::new(
+            format!("{} is signed by {}", chain_name(idx), chain_name(idx + 1)),
+            verify_issued_by(&pair[0], &pair[1]),
+        ));
+    }
+    let last = chain.len() - 1;
+    let anchor = bundle
+        .iter()
+        .position(|ca| verify_issued_by(&chain[last], ca).is_ok());
+    checks.push(Check::new(
+        format!("{} chains to a CA in the bundle", chain_name(last)),
+        anchor.map(|_| ()).ok_or_else(|| {
+            anyhow!(
+                "no bundle CA issued it (issuer {})",
+                chain[last].tbs_certificate.issuer
+            )
+        }),
+    ));
+
+    // Full path from the leaf up to the anchor, used for the remaining checks.
+    let mut path: Vec<&Certificate> = chain.iter().collect();
+    if let Some(anchor) = anchor {
+        path.push(&bundle[anchor]);
+    }
+
+    checks.push(Check::new(
+        "validity windows nest",
+        check_validity_nesting(&path),
+    ));
+    checks.push(Check::new(
+        format!("chain is valid at {}", now.format("%Y-%m-%d %H:%M:%S UTC")),
+        check_currently_valid(&path, now),
+    ));
+    for (idx, ca) in path.iter().enumerate().skip(1) {
+        // Number of intermediate CAs between this CA and the leaf.
+        let below = idx - 1;
+        let name = if idx < chain.len() {
+            chain_name(idx)
+        } else {
+            "bundle CA".to_string()
+        };
+        checks.push(Check::new(
+            format!("{name} CA constraints"),
+            check_ca_constraints(ca, below),
+        ));
+    }
+    checks.push(Check::new("leaf is not a CA", check_leaf_not_ca(&chain[0])));
+    checks.push(Check::new(
+        "leaf key matches x509_svid_key",
+        check_key_matches(&chain[0], svid.private_key()),
+    ));
+    checks.push(Check::new(
+        format!("leaf URI SAN is {}", svid.spiffe_id()),
+        check_spiffe_id(&chain[0], svid.spiffe_id()),
+    ));
+
+    checks
+}
+
+fn chain_name(idx: usize) -> String {
+    if idx == 0 {
+        "leaf".to_string()
+    } else {
+        format!("intermediate #{idx}")
+    }
+}
+
+fn verify_issued_by(cert: &Certificate, issuer: &Certificate) -> Result<()> {
+    if cert.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        anyhow::bail!(
+            "issuer {} does not match subject {}",
+            cert.tbs_certificate.issuer,
+            issuer.tbs_certificate.subject
+        );
+    }
+
+    let spki = &issuer.tbs_certificate.subject_public_key_info;
+    let algorithm = signature_algorithm(cert, issuer)?;
+    let public_key = spki
+        .subject_public_key
+        .as_bytes()
+        .ok_or_else(|| anyhow!("issuer public key is not byte aligned"))?;
+    let message = cert
+        .tbs_certificate
+        .to_der()
+        .context("failed to encode certificate")?;
+    let sig = cert
+        .signature
+        .as_bytes()
+        .ok_or_else(|| anyhow!("signature is not byte aligned"))?;
+
+    UnparsedPublicKey::new(algorithm, public_key)
+        .verify(&message, sig)
+        .map_err(|_| anyhow!("signature does not verify"))
+}
+
+fn signature_algorithm(
+    cert: &Certificate,
+    issuer: &Certificate,
+) -> Result<&'static dyn VerificationAlgorithm> {
+    let spki = &issuer.tbs_certificate.subject_public_key_info;
+    let curve = if spki.algorithm.oid == ID_EC_PUBLIC_KEY {
+        Some(ec_curve(spki)?)
+    } else {
+        None
+    };
+
+    Ok(match (cert.signature_algorithm.oid, curve) {
+        (ECDSA_WITH_SHA_256, Some(SECP_256_R_1)) => &signature::ECDSA_P256_SHA256_ASN1,
+        (ECDSA_WITH_SHA_256, Some(SECP_384_R_1)) => &signature::ECDSA_P384_SHA256_ASN1,
+        (ECDSA_WITH_SHA_384, Some(SECP_256_R_1)) => &signature::ECDSA_P256_SHA384_ASN1,
+        (ECDSA_WITH_SHA_384, Some(SECP_384_R_1)) => &signature::ECDSA_P384_SHA384_ASN1,
+        (SHA_256_WITH_RSA_ENCRYPTION, None) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        (SHA_384_WITH_RSA_ENCRYPTION, None) => &signature::RSA_PKCS1_2048_8192_SHA384,
+        (SHA_512_WITH_RSA_ENCRYPTION, None) => &signature::RSA_PKCS1_2048_8192_SHA512,
+        (ID_ED_25519, None) => &signature::ED25519,
+        (oid, _) => anyhow::bail!("unsupported signature algorithm {oid}"),
+    })
+}
+
+fn check_validity_nesting(path: &[&Certificate]) -> Result<()> {
+    for pair in path.windows(2) {
+        let (cert, issuer) = (&pair[0].tbs_certificate, &pair[1].tbs_certificate);
+        let (not_before, not_after) = validity(pair[0]);
+        let (issuer_not_before, issuer_not_after) = validity(pair[1]);
+
+        if not_before < issuer_not_before || not_after > issuer_not_after {
+            anyhow::bail!(
+                "{} is valid {} to {}, outside its issuer {} ({} to {})",
+                cert.subject,
+                not_before,
+                not_after,
+                issuer.subject,
+                issuer_not_before,
+                issuer_not_after
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_currently_valid(path: &[&Certificate], now: DateTime<Utc>) -> Result<()> {
+    for cert in path {
+        let (not_before, not_after) = validity(cert);
+        if now < not_before {
+            anyhow::bail!(
+                "{} is not valid before {}",
+                cert.tbs_certificate.subject,
+                not_before
+            );
+        }
+        if now > not_after {
+            anyhow::bail!("{} expired at {}", cert.tbs_certificate.subject, not_after);
+        }
+    }
+    Ok(())
+}
+
+fn check_ca_constraints(ca: &Certificate, intermediates_below: usize) -> Result<()> {
+    let constraints = find_extension::<BasicConstraints>(ca)?
+        .ok_or_else(|| anyhow!("basic constraints extension is missing"))?;
+    if !constraints.ca {
+        anyhow::bail!("basic constraints do not allow it to act as a CA");
+    }
+    if let Some(path_len) = constraints.path_len_constraint
+        && intermediates_below > usize::from(path_len)
+    {
+        anyhow::bail!(
+            "path length constraint {path_len} exceeded by {intermediates_below} intermediate(s)"
+        );
+    }
+    if let Some(key_usage) = find_extension::<KeyUsage>(ca)?
+        && !key_usage.key_cert_sign()
+    {
+        anyhow::bail!("key usage does not include certificate signing");
+    }
+    Ok(())
+}
+
+fn check_leaf_not_ca(leaf: &Certificate) -> Result<()> {
+    match find_extension::<BasicConstraints>(leaf)? {
+        Some(constraints) if constraints.ca => anyhow::bail!("leaf has CA:TRUE"),
+        _ => Ok(()),
+    }
+}
+
+fn check_key_matches(leaf: &Certificate, pkcs8: &[u8]) -> Result<()> {
+    let spki = &leaf.tbs_certificate.subject_public_key_info;
+    let leaf_key = spki
+        .subject_public_key
+        .as_bytes()
+        .ok_or_else(|| anyhow!("leaf public key is not byte aligned"))?;
+
+    let private_public_key = match spki.algorithm.oid {
+        ID_EC_PUBLIC_KEY => {
+            let algorithm = match ec_curve(spki)? {
+                SECP_256_R_1 => &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+                SECP_384_R_1 => &signature::ECDSA_P384_SHA384_ASN1_SIGNING,
+                other => anyhow::bail!("unsupported EC curve {other}"),
+            };
+            EcdsaKeyPair::from_pkcs8(algorithm, pkcs8, &SystemRandom::new())
+                .map_err(|err| anyhow!("failed to parse private key: {err}"))?
+                .public_key()
+                .as_ref()
+                .to_vec()
+        }
+        RSA_ENCRYPTION => RsaKeyPair::from_pkcs8(pkcs8)
+            .map_err(|err| anyhow!("failed to parse private key: {err}"))?
+            .public_key()
+            .as_ref()
+            .to_vec(),
+        ID_ED_25519 => Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+            .map_err(|err| anyhow!("failed to parse private key: {err}"))?
+            .public_key()
+            .as_ref()
+            .to_vec(),
+        other => anyhow::bail!("unsupported public key algorithm {other}"),
+    };
+
+    if private_public_key != leaf_key {
+        anyhow::bail!("private key does not correspond to the leaf certificate");
+    }
+    Ok(())
+}
+
+fn check_spiffe_id(leaf: &Certificate, spiffe_id: &SpiffeId) -> Result<()> {
+    let uris: Vec<String> = find_extension::<SubjectAltName>(leaf)?
+        .map(|san| san.0)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|name| match name {
+            GeneralName::UniformResourceIdentifier(uri) => Some(uri.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    match uris.as_slice() {
+        [uri] if spiffe_id == uri.as_str() => Ok(()),
+        [uri] if SpiffeId::new(uri).is_ok() => {
+            anyhow::bail!("leaf URI SAN {uri} does not match {spiffe_id}")
+        }
+        [uri] => anyhow::bail!("leaf URI SAN {uri} is not a SPIFFE ID"),
+        [] => anyhow::bail!("leaf has no URI SAN"),
+        _ => anyhow::bail!("leaf has {} URI SANs, expected exactly one", uris.len()),
+    }
+}
+
+fn validity(cert: &Certificate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let validity = &cert.tbs_certificate.validity;
+    (
+        parse_x509_time(&validity.not_before),
+        parse_x509_time(&validity.not_after),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use rcgen::{CertificateParams, KeyPair};
+
+    use spire_agent::{X509Bundle, X509Svid};
+
+    use super::verify_svid;
+    use spire_agent::testing::{TestCa, leaf_params};
+
+    const SPIFFE_ID: &str = "spiffe://example.org/workload";
+
+    // Builds root -> intermediate -> leaf and returns the SVID and its bundle.
+    fn svid_with(
+        leaf: CertificateParams,
+        intermediate_path_len: Option<u8>,
+    ) -> (X509Svid, X509Bundle) {
+        let root = TestCa::new("root");
+        let intermediate = root.intermediate("intermediate", intermediate_path_len);
+        let leaf = intermediate.issue(leaf);
+
+        let chain = vec![leaf.der, intermediate.der().to_vec()];
+        (
+            X509Svid::new(SPIFFE_ID.parse().unwrap(), chain, leaf.key).unwrap(),
+            root.bundle("example.org"),
+        )
+    }
+
+    fn failures((svid, bundle): &(X509Svid, X509Bundle)) -> Vec<String> {
+        verify_svid(svid, bundle, Utc::now())
+            .into_iter()
+            .filter(|check| !check.passed())
+            .map(|check| check.name)
+            .collect()
+    }
+
+    #[test]
+    fn verify_svid_passes_valid_chain() {
+        let svid = svid_with(leaf_params(SPIFFE_ID), Some(0));
+        assert_eq!(failures(&svid), Vec::<String>::new());
+    }
+
+    #[test]
+    fn verify_svid_detects_mismatched_key() {
+        let (svid, bundle) = svid_with(leaf_params(SPIFFE_ID), None);
+        let key = KeyPair::generate().unwrap().serialize_der();
+        let svid =
+            X509Svid::new(svid.spiffe_id().clone(), svid.cert_chain().to_vec(), key).unwrap();
+        assert_eq!(failures(&(svid, bundle)), ["leaf key matches x509_svid_key"]);
+    }
+
+    #[test]
+    fn verify_svid_detects_wrong_spiffe_id() {
+        let svid = svid_with(leaf_params("spiffe://example.org/other"), None);
+        assert_eq!(failures(&svid), [format!("leaf URI SAN is {SPIFFE_ID}")]);
+    }
+
+    #[test]
+    fn verify_svid_detects_foreign_bundle() {
+        let (svid, _) = svid_with(leaf_params(SPIFFE_ID), None);
+        let (_, foreign_bundle) = svid_with(leaf_params(SPIFFE_ID), None);
+        assert!(
+            failures(&(svid, foreign_bundle)).contains(&"intermediate #1 chains to a CA in the bundle".to_string())
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use spire_agent::grpc::X509svidResponse;
+use spire_agent::{WorkloadApiClient, X509Context};
+
+use crate::fetch_x509::{
+    X509SvidResponseJson, format_duration_seconds, format_utc_time, print_crls,
+    print_federated_bundles, print_svid, svid_bundle,
+};
+use crate::output::{OutputFormat, print_json};
+use crate::retry::{Reconnect, RetryPolicy};
+
+/// Prints every X.509 update until interrupted. `retry` decides whether a
+/// failure before the first update ends the command.
+pub async fn watch_x509(
+    socket_path: &str,
+    timeout: Duration,
+    retry: &RetryPolicy,
+    silent: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => {
+            result.context("failed to listen for interrupt signal")
+        }
+        result = watch_loop(socket_path, timeout, retry, silent, output) => result,
+    }
+}
+
+async fn watch_loop(
+    socket_path: &str,
+    timeout: Duration,
+    retry: &RetryPolicy,
+    silent: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut reconnect = Reconnect::new(retry);
+
+    loop {
+        match stream_updates(socket_path, timeout, silent, output, &mut reconnect).await {
+            Ok(()) => eprintln!("Workload API stream closed by the agent"),
+            Err(e) if reconnect.gives_up_on(&e) => return Err(e),
+            Err(e) => eprintln!("Error: {e:#}"),
+        }
+
+        let delay = reconnect.next_delay();
+        eprintln!("Reconnecting in {}", format_duration_seconds(delay));
+        tokio::time::sleep(delay).await;
+    }
+}
+
+// Holds a single FetchX509SVID stream open and prints every message until the
+// stream ends or fails. The backoff is reset once the agent delivers an update.
+async fn stream_updates(
+    socket_path: &str,
+    timeout: Duration,
+    silent: bool,
+    output: OutputFormat,
+    reconnect: &mut Reconnect,
+) -> Result<()> {
+    let mut last_update = Instant::now();
+    let mut client = tokio::time::timeout(timeout, WorkloadApiClient::connect(socket_path))
+        .await
+        .context("timed out connecting to the agent")??;
+    // JSON output re-emits each message as received, so the stream is not
+    // turned into contexts up front.
+    let mut stream = tokio::time::timeout(timeout, client.watch_x509_svid_responses())
+        .await
+        .context("request timed out")??;
+
+    while let Some(response) = stream.next().await? {
+        let context = X509Context::try_from(&response)?;
+        reconnect.connected();
+        if !silent {
+            print_update(&response, &context, last_update.elapsed(), output)?;
+        }
+        last_update = Instant::now();
+    }
+
+    Ok(())
+}
+
+fn print_update(
+    response: &X509svidResponse,
+    context: &X509Context,
+    elapsed: Duration,
+    output: OutputFormat,
+) -> Result<()> {
+    if output == OutputFormat::Json {
+        return print_json(&X509SvidResponseJson::from(response));
+    }
+
+    println!(
+        "[{}] Received {} svid after {}\n",
+        format_utc_time(Utc::now()),
+        context.svids().len(),
+        format_duration_seconds(elapsed)
+    );
+    for svid in context.svids() {
+        print_svid(svid, svid_bundle(context, svid)?)?;
+        println!();
+    }
+    print_federated_bundles(context.federated_bundles())?;
+    print_crls(context.bundles().crls())?;
+
+    Ok(())
+}
@@ -0,0 +1,396 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use der::{Decode, Reader, SliceReader};
+use x509_cert::Certificate;
+
+use crate::grpc::{X509BundlesResponse, X509svid, X509svidResponse};
+use crate::spiffe_id::{SpiffeId, TrustDomain};
+
+/// An X.509-SVID: a certificate chain for a SPIFFE ID and its private key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct X509Svid {
+    spiffe_id: SpiffeId,
+    cert_chain: Vec<Vec<u8>>,
+    private_key: Vec<u8>,
+    hint: String,
+}
+
+impl X509Svid {
+    /// Builds an SVID from DER certificates, leaf first, and a PKCS#8 DER
+    /// private key.
+    pub fn new(
+        spiffe_id: SpiffeId,
+        cert_chain: Vec<Vec<u8>>,
+        private_key: Vec<u8>,
+    ) -> Result<Self> {
+        if cert_chain.is_empty() {
+            anyhow::bail!("SVID {spiffe_id} has no certificates");
+        }
+        for (idx, cert) in cert_chain.iter().enumerate() {
+            Certificate::from_der(cert)
+                .with_context(|| format!("SVID {spiffe_id} has an invalid certificate #{idx}"))?;
+        }
+        if private_key.is_empty() {
+            anyhow::bail!("SVID {spiffe_id} has no private key");
+        }
+
+        Ok(Self {
+            spiffe_id,
+            cert_chain,
+            private_key,
+            hint: String::new(),
+        })
+    }
+
+    /// Like [`X509Svid::new`], but takes the chain as concatenated DER
+    /// certificates, which is how the Workload API carries it.
+    pub fn parse(spiffe_id: SpiffeId, cert_chain: &[u8], private_key: &[u8]) -> Result<Self> {
+        let cert_chain = split_certificates(cert_chain)
+            .with_context(|| format!("invalid certificate chain for {spiffe_id}"))?;
+        Self::new(spiffe_id, cert_chain, private_key.to_vec())
+    }
+
+    /// Sets the operator-provided hint that tells SVIDs for the same
+    /// workload apart.
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = hint.into();
+        self
+    }
+
+    pub fn spiffe_id(&self) -> &SpiffeId {
+        &self.spiffe_id
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        self.spiffe_id.trust_domain()
+    }
+
+    /// DER certificates, leaf first, followed by any intermediates.
+    pub fn cert_chain(&self) -> &[Vec<u8>] {
+        &self.cert_chain
+    }
+
+    /// The DER leaf certificate.
+    pub fn leaf(&self) -> &[u8] {
+        &self.cert_chain[0]
+    }
+
+    /// The PKCS#8 DER private key for the leaf certificate.
+    pub fn private_key(&self) -> &[u8] {
+        &self.private_key
+    }
+
+    /// The operator-provided hint; empty when the agent sent none.
+    pub fn hint(&self) -> &str {
+        &self.hint
+    }
+}
+
+impl TryFrom<&X509svid> for X509Svid {
+    type Error = anyhow::Error;
+
+    fn try_from(svid: &X509svid) -> Result<Self> {
+        let spiffe_id = SpiffeId::new(&svid.spiffe_id)
+            .with_context(|| format!("invalid SPIFFE ID {:?}", svid.spiffe_id))?;
+        Ok(Self::parse(spiffe_id, &svid.x509_svid, &svid.x509_svid_key)?.with_hint(&svid.hint))
+    }
+}
+
+/// The X.509 authorities of one trust domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct X509Bundle {
+    trust_domain: TrustDomain,
+    authorities: Vec<Vec<u8>>,
+}
+
+impl X509Bundle {
+    /// Builds a bundle from DER CA certificates.
+    pub fn new(trust_domain: TrustDomain, authorities: Vec<Vec<u8>>) -> Result<Self> {
+        for (idx, cert) in authorities.iter().enumerate() {
+            Certificate::from_der(cert).with_context(|| {
+                format!("bundle for {trust_domain} has an invalid certificate #{idx}")
+            })?;
+        }
+
+        Ok(Self {
+            trust_domain,
+            authorities,
+        })
+    }
+
+    /// Like [`X509Bundle::new`], but takes concatenated DER certificates.
+    pub fn parse(trust_domain: TrustDomain, authorities: &[u8]) -> Result<Self> {
+        let authorities = split_certificates(authorities)
+            .with_context(|| format!("invalid bundle for {trust_domain}"))?;
+        Self::new(trust_domain, authorities)
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    /// DER CA certificates trusted for the trust domain.
+    pub fn authorities(&self) -> &[Vec<u8>] {
+        &self.authorities
+    }
+}
+
+/// X.509 bundles keyed by trust domain, together with the certificate
+/// revocation lists the agent publishes alongside them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct X509BundleSet {
+    bundles: BTreeMap<TrustDomain, X509Bundle>,
+    crls: Vec<Vec<u8>>,
+}
+
+impl X509BundleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a bundle, replacing any previous one for the same trust domain.
+    pub fn insert(&mut self, bundle: X509Bundle) {
+        self.bundles.insert(bundle.trust_domain.clone(), bundle);
+    }
+
+    pub fn get(&self, trust_domain: &TrustDomain) -> Option<&X509Bundle> {
+        self.bundles.get(trust_domain)
+    }
+
+    /// Bundles in trust domain order.
+    pub fn iter(&self) -> impl Iterator<Item = &X509Bundle> {
+        self.bundles.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bundles.is_empty()
+    }
+
+    /// DER certificate revocation lists.
+    pub fn crls(&self) -> &[Vec<u8>] {
+        &self.crls
+    }
+}
+
+impl TryFrom<&X509BundlesResponse> for X509BundleSet {
+    type Error = anyhow::Error;
+
+    fn try_from(resp: &X509BundlesResponse) -> Result<Self> {
+        let mut set = Self {
+            bundles: BTreeMap::new(),
+            crls: parse_crls(&resp.crl)?,
+        };
+        for (trust_domain, bundle) in &resp.bundles {
+            set.insert(X509Bundle::parse(
+                parse_trust_domain(trust_domain)?,
+                bundle,
+            )?);
+        }
+        Ok(set)
+    }
+}
+
+/// Everything one `FetchX509SVID` message carries: the workload's SVIDs and
+/// the bundles needed to verify peers, including federated ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct X509Context {
+    svids: Vec<X509Svid>,
+    bundles: X509BundleSet,
+}
+
+impl X509Context {
+    pub fn new(svids: Vec<X509Svid>, bundles: X509BundleSet) -> Self {
+        Self { svids, bundles }
+    }
+
+    /// SVIDs in the order the agent sent them.
+    pub fn svids(&self) -> &[X509Svid] {
+        &self.svids
+    }
+
+    /// The first SVID, which the Workload API designates as the default.
+    pub fn default_svid(&self) -> Option<&X509Svid> {
+        self.svids.first()
+    }
+
+    /// Bundles for the SVIDs' own trust domains and for federated ones.
+    pub fn bundles(&self) -> &X509BundleSet {
+        &self.bundles
+    }
+
+    /// The bundle of the trust domain that issued `svid`.
+    pub fn bundle_for(&self, svid: &X509Svid) -> Option<&X509Bundle> {
+        self.bundles.get(svid.trust_domain())
+    }
+
+    /// Bundles of trust domains none of the SVIDs belong to.
+    pub fn federated_bundles(&self) -> impl Iterator<Item = &X509Bundle> {
+        self.bundles.iter().filter(|bundle| {
+            !self
+                .svids
+                .iter()
+                .any(|svid| svid.trust_domain() == bundle.trust_domain())
+        })
+    }
+}
+
+impl TryFrom<&X509svidResponse> for X509Context {
+    type Error = anyhow::Error;
+
+    fn try_from(resp: &X509svidResponse) -> Result<Self> {
+        let mut bundles = X509BundleSet {
+            bundles: BTreeMap::new(),
+            crls: parse_crls(&resp.crl)?,
+        };
+        for (trust_domain, bundle) in &resp.federated_bundles {
+            bundles.insert(X509Bundle::parse(
+                parse_trust_domain(trust_domain)?,
+                bundle,
+            )?);
+        }
+
+        let mut svids = Vec::with_capacity(resp.svids.len());
+        for svid in &resp.svids {
+            let parsed = X509Svid::try_from(svid)?;
+            // Each SVID carries the bundle of its own trust domain, which
+            // takes precedence over a federated copy.
+            bundles.insert(X509Bundle::parse(
+                parsed.trust_domain().clone(),
+                &svid.bundle,
+            )?);
+            svids.push(parsed);
+        }
+
+        Ok(Self { svids, bundles })
+    }
+}
+
+/// Splits concatenated DER certificates without decoding them.
+pub fn split_certificates(der_bytes: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut certs = Vec::new();
+    let mut reader = SliceReader::new(der_bytes).context("failed to create DER reader")?;
+
+    while !reader.is_finished() {
+        let cert = reader.tlv_bytes().context("failed to read certificate")?;
+        certs.push(cert.to_vec());
+    }
+
+    Ok(certs)
+}
+
+// Checks every CRL once when it arrives, so that a malformed one is reported
+// with the update instead of failing each TLS handshake that consults it.
+fn parse_crls(crls: &[Vec<u8>]) -> Result<Vec<Vec<u8>>> {
+    for (idx, crl) in crls.iter().enumerate() {
+        webpki::BorrowedCertRevocationList::from_der(crl)
+            .with_context(|| format!("invalid CRL #{}", idx + 1))?;
+    }
+    Ok(crls.to_vec())
+}
+
+// The Workload API keys bundle maps by trust domain ID
+// (`spiffe://example.org`), but accept a bare name too.
+pub(crate) fn parse_trust_domain(key: &str) -> Result<TrustDomain> {
+    TrustDomain::new(key).with_context(|| format!("invalid trust domain {key:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{X509Context, X509Svid, split_certificates};
+    use crate::grpc::{X509svid, X509svidResponse};
+    use crate::spiffe_id::SpiffeId;
+    use crate::testing::{TestCa, TestCert, leaf_params, self_signed};
+
+    #[test]
+    fn split_certificates_reads_concatenated_certs() {
+        let cert1 = TestCa::new("a").der().to_vec();
+        let cert2 = TestCa::new("b").der().to_vec();
+
+        let certs = split_certificates(&[cert1.clone(), cert2.clone()].concat()).unwrap();
+        assert_eq!(certs, [cert1, cert2]);
+        assert!(split_certificates(&[0x30, 0x05, 0x01]).is_err());
+    }
+
+    #[test]
+    fn x509_context_splits_own_and_federated_bundles() {
+        let ca = TestCa::new("root");
+        let leaf = ca.issue(leaf_params("spiffe://example.org/workload"));
+        let root = ca.der().to_vec();
+        let federated = TestCa::new("federated").der().to_vec();
+        let crl = ca.crl(&[]);
+        let resp = X509svidResponse {
+            svids: vec![X509svid {
+                spiffe_id: "spiffe://example.org/workload".to_string(),
+                x509_svid: leaf.der.clone(),
+                x509_svid_key: leaf.key,
+                bundle: root.clone(),
+                hint: "internal".to_string(),
+            }],
+            crl: vec![crl.clone()],
+            federated_bundles: [("spiffe://other.org".to_string(), federated.clone())]
+                .into_iter()
+                .collect(),
+        };
+
+        let context = X509Context::try_from(&resp).unwrap();
+        let svid = context.default_svid().unwrap();
+        assert_eq!(svid.spiffe_id(), "spiffe://example.org/workload");
+        assert_eq!(svid.trust_domain(), "example.org");
+        assert_eq!(svid.hint(), "internal");
+        assert_eq!(svid.cert_chain(), [leaf.der]);
+        assert_eq!(context.bundle_for(svid).unwrap().authorities(), [root]);
+
+        let federated_bundles: Vec<_> = context.federated_bundles().collect();
+        assert_eq!(federated_bundles.len(), 1);
+        assert_eq!(federated_bundles[0].trust_domain(), "other.org");
+        assert_eq!(federated_bundles[0].authorities(), [federated]);
+        assert_eq!(
+            context.bundles().get(&"other.org".parse().unwrap()),
+            Some(federated_bundles[0])
+        );
+        assert_eq!(context.bundles().crls(), [crl]);
+    }
+
+    #[test]
+    fn x509_context_rejects_malformed_crls() {
+        let ca = TestCa::new("root");
+        let leaf = ca.issue(leaf_params("spiffe://example.org/workload"));
+        let resp = X509svidResponse {
+            svids: vec![X509svid {
+                spiffe_id: "spiffe://example.org/workload".to_string(),
+                x509_svid: leaf.der,
+                x509_svid_key: leaf.key,
+                bundle: ca.der().to_vec(),
+                hint: String::new(),
+            }],
+            crl: vec![ca.crl(&[]), vec![0x01]],
+            ..Default::default()
+        };
+
+        let err = X509Context::try_from(&resp).unwrap_err();
+        assert_eq!(err.to_string(), "invalid CRL #2");
+    }
+
+    #[test]
+    fn x509_svid_rejects_incomplete_material() {
+        let TestCert { der: leaf, key } = self_signed(leaf_params("spiffe://example.org/w"));
+        let id: SpiffeId = "spiffe://example.org/w".parse().unwrap();
+
+        assert!(X509Svid::new(id.clone(), vec![], key.clone()).is_err());
+        assert!(X509Svid::new(id.clone(), vec![leaf.clone()], vec![]).is_err());
+        assert!(X509Svid::new(id, vec![vec![0x30, 0x00]], key.clone()).is_err());
+
+        let svid = X509svid {
+            spiffe_id: "example.org/w".to_string(),
+            x509_svid: leaf,
+            x509_svid_key: key,
+            ..Default::default()
+        };
+        assert!(X509Svid::try_from(&svid).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
+
+use crate::backoff::{Backoff, INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY, RECONNECT_JITTER};
+use crate::client::WorkloadApiClient;
+use crate::spiffe_id::SpiffeId;
+use crate::x509::{X509BundleSet, X509Context, X509Svid};
+
+/// Picks one SVID out of an [`X509Context`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SvidSelector {
+    /// The first SVID, which the Workload API designates as the default.
+    #[default]
+    Default,
+    /// The SVID carrying this operator-provided hint.
+    Hint(String),
+    /// The SVID issued to this SPIFFE ID.
+    SpiffeId(SpiffeId),
+}
+
+impl SvidSelector {
+    pub fn select<'a>(&self, context: &'a X509Context) -> Option<&'a X509Svid> {
+        match self {
+            Self::Default => context.default_svid(),
+            Self::Hint(hint) => context.svids().iter().find(|svid| svid.hint() == hint),
+            Self::SpiffeId(id) => context.svids().iter().find(|svid| svid.spiffe_id() == id),
+        }
+    }
+}
+
+/// Configures an [`X509Source`] before it starts streaming.
+#[derive(Debug, Clone)]
+pub struct X509SourceBuilder {
+    address: String,
+    selector: SvidSelector,
+    backoff: Backoff,
+}
+
+impl X509SourceBuilder {
+    /// Which SVID [`X509Source::svid`] returns; the default SVID unless set.
+    pub fn selector(mut self, selector: SvidSelector) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    /// Delay between reconnect attempts after the stream fails.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Starts the background task that keeps the stream open. Must be called
+    /// from within a Tokio runtime.
+    pub fn spawn(self) -> X509Source {
+        let (updates_tx, updates) = watch::channel(None);
+        let (shutdown_tx, shutdown) = oneshot::channel();
+        let last_error = Arc::new(Mutex::new(None));
+        let task = tokio::spawn(run(
+            self.address,
+            self.backoff,
+            updates_tx,
+            Arc::clone(&last_error),
+            shutdown,
+        ));
+
+        X509Source {
+            updates,
+            last_error,
+            selector: self.selector,
+            shutdown: Some(shutdown_tx),
+            task: Some(task),
+        }
+    }
+}
+
+/// The workload's X.509 identity, kept current from a `FetchX509SVID` stream.
+///
+/// The source connects once and holds the latest [`X509Context`]; when the
+/// stream fails or the agent closes it, the source reconnects with
+/// exponential backoff and keeps serving the last context in the meantime.
+/// Dropping the source stops the background task; [`X509Source::close`] also
+/// waits for it to finish.
+#[derive(Debug)]
+pub struct X509Source {
+    updates: watch::Receiver<Option<Arc<X509Context>>>,
+    last_error: Arc<Mutex<Option<String>>>,
+    selector: SvidSelector,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl X509Source {
+    /// A builder for a source reading from a Workload API address: a socket
+    /// path, `unix:///path` or `tcp://ip:port`.
+    pub fn builder(address: impl Into<String>) -> X509SourceBuilder {
+        X509SourceBuilder {
+            address: address.into(),
+            selector: SvidSelector::Default,
+            backoff: Backoff::new(INITIAL_RECONNECT_DELAY, MAX_RECONNECT_DELAY)
+                .with_jitter(RECONNECT_JITTER),
+        }
+    }
+
+    /// Starts a source with the default selector and backoff, and waits up
+    /// to `timeout` for the first context.
+    pub async fn connect(address: impl Into<String>, timeout: Duration) -> Result<Self> {
+        let source = Self::builder(address).spawn();
+        source.wait_ready(timeout).await?;
+        Ok(source)
+    }
+
+    /// Waits until the first context has arrived. On timeout the error
+    /// includes the last connection or stream failure, if any.
+    pub async fn wait_ready(&self, timeout: Duration) -> Result<Arc<X509Context>> {
+        let mut updates = self.updates.clone();
+        let ready = tokio::time::timeout(timeout, updates.wait_for(Option::is_some)).await;
+
+        match ready {
+            Ok(Ok(context)) => Ok(Arc::clone(context.as_ref().expect("context is set"))),
+            Ok(Err(_)) => Err(anyhow!("X509 source is closed")),
+            Err(_) => {
+                let err = match self.last_error() {
+                    Some(last) => anyhow!(last),
+                    None => anyhow!("no response from the agent"),
+                };
+                Err(err.context(format!("no X509-SVID received within {timeout:?}")))
+            }
+        }
+    }
+
+    /// The latest context, or `None` before the first one arrives.
+    pub fn context(&self) -> Option<Arc<X509Context>> {
+        self.updates.borrow().clone()
+    }
+
+    /// The SVID picked by the configured selector from the latest context.
+    pub fn svid(&self) -> Result<X509Svid> {
+        self.select_svid(&self.selector)
+    }
+
+    /// The SVID picked by `selector` from the latest context.
+    pub fn select_svid(&self, selector: &SvidSelector) -> Result<X509Svid> {
+        let context = self.ready_context()?;
+        selector
+            .select(&context)
+            .cloned()
+            .with_context(|| format!("no X509-SVID matches {selector:?}"))
+    }
+
+    /// The bundles from the latest context.
+    pub fn bundles(&self) -> Result<X509BundleSet> {
+        Ok(self.ready_context()?.bundles().clone())
+    }
+
+    /// A receiver that yields every context from now on.
+    pub fn subscribe(&self) -> X509ContextUpdates {
+        let mut updates = self.updates.clone();
+        updates.mark_unchanged();
+        X509ContextUpdates { updates }
+    }
+
+    /// The last connection or stream failure since the last good update.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    /// Stops streaming and waits for the background task to exit.
+    pub async fn close(mut self) {
+        self.shutdown.take();
+        if let Some(task) = self.task.take() {
+            // The task only ends by returning; a panic has already been
+            // reported by the runtime.
+            let _ = task.await;
+        }
+    }
+
+    fn ready_context(&self) -> Result<Arc<X509Context>> {
+        self.context()
+            .context("X509 source has not received an X509-SVID yet")
+    }
+}
+
+/// Context updates from an [`X509Source`].
+#[derive(Debug, Clone)]
+pub struct X509ContextUpdates {
+    updates: watch::Receiver<Option<Arc<X509Context>>>,
+}
+
+impl X509ContextUpdates {
+    /// The latest context, without waiting; `None` before the first one.
+    pub fn latest(&self) -> Option<Arc<X509Context>> {
+        self.updates.borrow().clone()
+    }
+
+    /// Waits for the next context; `None` once the source is closed.
+    ///
+    /// Updates that arrive faster than they are read are coalesced, so the
+    /// context returned is always the latest one.
+    pub async fn changed(&mut self) -> Option<Arc<X509Context>> {
+        loop {
+            self.updates.changed().await.ok()?;
+            if let Some(context) = self.updates.borrow_and_update().clone() {
+                return Some(context);
+            }
+        }
+    }
+}
+
+async fn run(
+    address: String,
+    mut backoff: Backoff,
+    updates: watch::Sender<Option<Arc<X509Context>>>,
+    last_error: Arc<Mutex<Option<String>>>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    loop {
+        let result = tokio::select! {
+            _ = &mut shutdown => return,
+            result = stream_contexts(&address, &updates, &last_error, &mut backoff) => result,
+        };
+        let err = result
+            .err()
+            .unwrap_or_else(|| anyhow!("Workload API stream closed by the agent"));
+        last_error.lock().unwrap().replace(format!("{err:#}"));
+
+        tokio::select! {
+            _ = &mut shutdown => return,
+            () = tokio::time::sleep(backoff.next_delay()) => {}
+        }
+    }
+}
+
+// Publishes every context until the stream ends; `Ok` means the agent
+// closed it.
+async fn stream_contexts(
+    address: &str,
+    updates: &watch::Sender<Option<Arc<X509Context>>>,
+    last_error: &Mutex<Option<String>>,
+    backoff: &mut Backoff,
+) -> Result<()> {
+    let mut client = WorkloadApiClient::connect(address).await?;
+    let mut stream = client.watch_x509_contexts().await?;
+
+    while let Some(context) = stream.next().await? {
+        backoff.reset();
+        last_error.lock().unwrap().take();
+        updates.send_replace(Some(Arc::new(context)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::sync::mpsc;
+    use tonic::Status;
+
+    use super::{SvidSelector, X509Source};
+    use crate::backoff::Backoff;
+    use crate::grpc::{X509svid, X509svidResponse};
+    use crate::testing::{start_agent, x509_svid as svid};
+
+    fn response(svids: Vec<X509svid>) -> Result<X509svidResponse, Status> {
+        Ok(X509svidResponse {
+            svids,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn x509_source_follows_rotations_and_reconnects() {
+        let agent = start_agent(Duration::from_secs(60)).await;
+        let streams = agent.x509_streams;
+        let source = X509Source::builder(agent.address)
+            .selector(SvidSelector::Hint("internal".to_string()))
+            .backoff(Backoff::new(
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+            ))
+            .spawn();
+        assert!(source.svid().is_err());
+
+        let (first_tx, first) = mpsc::channel(4);
+        streams.send(first).await.unwrap();
+        first_tx
+            .send(response(vec![
+                svid("spiffe://example.org/a", "external"),
+                svid("spiffe://example.org/b", "internal"),
+            ]))
+            .await
+            .unwrap();
+
+        let context = source.wait_ready(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(context.svids().len(), 2);
+        assert_eq!(source.svid().unwrap().spiffe_id(), "spiffe://example.org/b");
+        let by_id = SvidSelector::SpiffeId("spiffe://example.org/a".parse().unwrap());
+        assert_eq!(source.select_svid(&by_id).unwrap().hint(), "external");
+        let trust_domain = "example.org".parse().unwrap();
+        assert!(source.bundles().unwrap().get(&trust_domain).is_some());
+
+        // A rotation on the open stream reaches subscribers.
+        let mut updates = source.subscribe();
+        first_tx
+            .send(response(vec![svid("spiffe://example.org/c", "internal")]))
+            .await
+            .unwrap();
+        let context = updates.changed().await.unwrap();
+        assert_eq!(context.svids()[0].spiffe_id(), "spiffe://example.org/c");
+
+        // The agent closing the stream leads to a new one.
+        drop(first_tx);
+        let (second_tx, second) = mpsc::channel(4);
+        streams.send(second).await.unwrap();
+        second_tx
+            .send(response(vec![svid("spiffe://example.org/d", "internal")]))
+            .await
+            .unwrap();
+        let context = updates.changed().await.unwrap();
+        assert_eq!(context.svids()[0].spiffe_id(), "spiffe://example.org/d");
+        assert_eq!(source.svid().unwrap().spiffe_id(), "spiffe://example.org/d");
+
+        source.close().await;
+        assert!(updates.changed().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn x509_source_wait_ready_reports_last_error() {
+        let dir = std::env::temp_dir().join(format!("x509-source-test-{}", std::process::id()));
+        let socket = dir.join("missing.sock");
+        let source = X509Source::builder(socket.to_str().unwrap())
+            .backoff(Backoff::new(
+                Duration::from_millis(10),
+                Duration::from_millis(10),
+            ))
+            .spawn();
+
+        let err = source
+            .wait_ready(Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("failed to connect to"),
+            "{err:#}"
+        );
+        assert!(source.last_error().is_some());
+    }
+}